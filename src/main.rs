@@ -1,40 +1,117 @@
 use eyre::OptionExt;
 use std::env;
-use std::fs::File;
+use std::fs;
+use std::io::{IsTerminal, Read};
 use tuiflow_app::App;
-use tuiflow_app::configuration::AppConfiguration;
+use tuiflow_app::busy_indicator::BusyCommandRunner;
+use tuiflow_app::configuration::{AppConfiguration, LaunchArguments};
 use tuiflow_app::factory::WorkflowFactory;
+use tuiflow_app::migration;
 use tuiflow_model::transition::Transition;
 use tuiflow_model::variable_mapping::RegexVariableExtractor;
 use tuiflow_sh::sh_command_runner::ShCommandRunner;
 
+const USAGE: &str = "Usage: tuiflow <path_to_config> [<argument>...] [--set <name>=<value>...]
+       tuiflow migrate <path_to_config>";
+
 pub fn main() -> eyre::Result<()> {
+    if env::args().nth(1).as_deref() == Some("migrate") {
+        return migrate_config_or_print_err();
+    }
+
     let tuiflow_config_path = read_config_path_or_print_err()?;
     let config = read_config_or_print_err(tuiflow_config_path)?;
+    let launch_arguments = read_launch_arguments_or_print_err()?;
 
-    let terminal = ratatui::init();
-    App::<Transition<ShCommandRunner, RegexVariableExtractor>, WorkflowFactory<ShCommandRunner>>::new(config)?.run(terminal)?;
-    ratatui::restore();
+    let command_activity = ShCommandRunner::get_command_activity();
+    type Runner = BusyCommandRunner<ShCommandRunner>;
+    let flow_exit = App::<Transition<Runner, RegexVariableExtractor>, WorkflowFactory<Runner>>::new(
+        config,
+        launch_arguments,
+        command_activity,
+    )?
+    .run()?;
+    if let Some(flow_exit) = flow_exit {
+        println!("{}", flow_exit.output);
+        std::process::exit(flow_exit.exit_code.into());
+    }
     Ok(())
 }
 
 fn read_config_path_or_print_err() -> eyre::Result<String> {
     let config_path = env::args()
         .nth(1)
-        .ok_or_eyre("Invalid arguments. Usage: tuiflow <path_to_config>")?;
+        .ok_or_eyre(format!("Invalid arguments. {USAGE}"))?;
     Ok(config_path)
 }
 
 fn read_config_or_print_err(config_path: String) -> eyre::Result<AppConfiguration> {
-    let file = File::open(&config_path).map_err(|e| {
-        println!("Error: Could not open configuration file: {}. Check if the file exists and is accessible.", config_path);
-        e
-    })?;
-
-    let config: AppConfiguration = serde_yaml::from_reader(file).map_err(|e| {
+    let flow_file = read_flow_file_or_print_err(&config_path)?;
+    let config = migration::parse_flow_file(&flow_file).inspect_err(|_| {
         println!("Error: Could not parse configuration file.");
-        e
     })?;
 
     Ok(config)
 }
+
+/// Rewrites the flow file in place, upgraded to the current flow file version.
+fn migrate_config_or_print_err() -> eyre::Result<()> {
+    let config_path = env::args()
+        .nth(2)
+        .ok_or_eyre(format!("Invalid arguments. {USAGE}"))?;
+    let flow_file = read_flow_file_or_print_err(&config_path)?;
+    let migrated_flow_file = migration::migrate_flow_file(&flow_file).inspect_err(|_| {
+        println!("Error: Could not migrate configuration file.");
+    })?;
+    fs::write(&config_path, migrated_flow_file).inspect_err(|_| {
+        println!("Error: Could not write configuration file: {}.", config_path);
+    })?;
+    println!("Migrated {} to flow file version {}.", config_path, migration::CURRENT_FLOW_VERSION);
+    Ok(())
+}
+
+fn read_flow_file_or_print_err(config_path: &str) -> eyre::Result<String> {
+    let flow_file = fs::read_to_string(config_path).inspect_err(|_| {
+        println!("Error: Could not open configuration file: {}. Check if the file exists and is accessible.", config_path);
+    })?;
+    Ok(flow_file)
+}
+
+/// Positional arguments become the variables `<arg1>`, `<arg2>`, ... and `--set <name>=<value>` becomes `<name>`.
+/// Piped stdin replaces the output of the initial command, unless it is empty.
+fn read_launch_arguments_or_print_err() -> eyre::Result<LaunchArguments> {
+    let mut variables = vec![];
+    let mut positional_argument_count = 0;
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        let assignment = match arg.strip_prefix("--set") {
+            Some("") => Some(args.next().ok_or_eyre(format!("Invalid arguments. {USAGE}"))?),
+            Some(inline_assignment) => inline_assignment.strip_prefix('=').map(str::to_string),
+            None => None,
+        };
+        match assignment {
+            Some(assignment) => {
+                let (name, value) = assignment
+                    .split_once('=')
+                    .ok_or_eyre(format!("Invalid variable assignment '{assignment}'. {USAGE}"))?;
+                variables.push((name.to_string(), value.to_string()));
+            }
+            None => {
+                positional_argument_count += 1;
+                variables.push((format!("arg{positional_argument_count}"), arg));
+            }
+        }
+    }
+
+    let mut stdin = None;
+    if !std::io::stdin().is_terminal() {
+        let mut piped_input = String::new();
+        std::io::stdin().read_to_string(&mut piped_input).inspect_err(|_| {
+            println!("Error: Could not read from stdin.");
+        })?;
+        // e.g. cron or CI hand over an empty stdin, the initial command runs then as without a pipe
+        stdin = Some(piped_input).filter(|piped_input| !piped_input.trim().is_empty());
+    }
+
+    Ok(LaunchArguments { variables, stdin })
+}
@@ -0,0 +1,204 @@
+use crate::terminal::TtyTerminal;
+use crossterm::event::{self, Event};
+use ratatui::buffer::Buffer;
+use ratatui::widgets::WidgetRef;
+use std::cell::RefCell;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use tuiflow_model_contracts::command_activity::{CommandActivity, RunningCommand};
+use tuiflow_model_contracts::command_runner::{CommandOutput, CommandRunner, CommandRunnerError};
+use tuiflow_model_contracts::control::Control;
+use tuiflow_model_contracts::output_stream::OutputStream;
+use tuiflow_ui::io::key_event_to_model_mapping::key_event_to_key;
+use tuiflow_ui::title_bar_widget::BusyIndicatorWidget;
+
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SHOW_AFTER: Duration = Duration::from_millis(150); // fast commands finish without flickering
+const TICK: Duration = Duration::from_millis(80);
+
+thread_local! {
+    // runners are created without context, so the flow work tells the runners on its thread where to show that they wait
+    static BUSY_SCREEN: RefCell<Option<BusyScreen>> = const { RefCell::new(None) };
+}
+
+/// Runs the commands of the runner it wraps on a worker thread. Meanwhile the thread of the flow shows a spinner
+/// with the running command and its elapsed time, if the flow runs inside `while_busy`.
+#[derive(Clone)]
+pub struct BusyCommandRunner<R: CommandRunner> {
+    command_runner: R,
+}
+
+impl<R: CommandRunner> CommandRunner for BusyCommandRunner<R> {
+    type Command = R::Command;
+
+    fn run_command(&self, command: &Self::Command) -> Result<CommandOutput, CommandRunnerError> {
+        wait_for(|| self.command_runner.run_command(command))
+    }
+
+    fn run_parallel_commands(&self, commands: &[Self::Command]) -> Vec<Result<CommandOutput, CommandRunnerError>> {
+        wait_for(|| self.command_runner.run_parallel_commands(commands))
+    }
+
+    fn run_interactive_command(&self, command: &Self::Command) -> Result<(), CommandRunnerError> {
+        self.command_runner.run_interactive_command(command)
+    }
+
+    fn run_streaming_command(&self, command: &Self::Command) -> Result<OutputStream, CommandRunnerError> {
+        self.command_runner.run_streaming_command(command)
+    }
+
+    fn new() -> Self {
+        Self {
+            command_runner: R::new(),
+        }
+    }
+
+    fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            command_runner: self.command_runner.with_timeout(timeout),
+        }
+    }
+
+    fn get_full_command(command: &Self::Command) -> String {
+        R::get_full_command(command)
+    }
+}
+
+/// Runs the work of the runner on a worker thread and returns its output through a channel. Only the runner
+/// and the command go to the worker, the flow stays on this thread.
+fn wait_for<O: Send>(work: impl FnOnce() -> O + Send) -> O {
+    thread::scope(|scope| {
+        let (output_sender, output_receiver) = mpsc::channel();
+        let worker = scope.spawn(move || _ = output_sender.send(work()));
+        let output = BUSY_SCREEN.with_borrow_mut(|busy_screen| match busy_screen {
+            Some(busy_screen) => busy_screen.show_until_received(&output_receiver),
+            None => output_receiver.recv().ok(),
+        });
+        // the worker ends without output only if the runner panicked
+        output.unwrap_or_else(|| std::panic::resume_unwind(worker.join().expect_err("The worker ended without output")))
+    })
+}
+
+/// Where runners on the thread of the flow show that they wait, over the last frame of the flow.
+struct BusyScreen {
+    terminal: TtyTerminal,
+    last_frame: Buffer,
+    command_activity: CommandActivity,
+    cancel_control: Control,
+    spinner_frame_index: usize,
+}
+
+/// Runs the work, which may run commands through a `BusyCommandRunner`, while the terminal shows the last frame
+/// with a spinner for a running command. The cancel key kills the command. Keys pressed in the meantime are dropped,
+/// so they don't run against a state the user has not seen yet. Returns the terminal for the next frame.
+pub(crate) fn while_busy<O>(
+    terminal: TtyTerminal,
+    last_frame: &Buffer,
+    command_activity: &CommandActivity,
+    cancel_control: &Control,
+    work: impl FnOnce() -> O,
+) -> (O, TtyTerminal) {
+    command_activity.clear_cancel_request();
+    BUSY_SCREEN.set(Some(BusyScreen {
+        terminal,
+        last_frame: last_frame.clone(),
+        command_activity: command_activity.clone(),
+        cancel_control: cancel_control.clone(),
+        spinner_frame_index: 0,
+    }));
+    let output = work();
+    let busy_screen = BUSY_SCREEN.take().expect("The busy screen is only taken by while_busy");
+    (output, busy_screen.terminal)
+}
+
+impl BusyScreen {
+    fn show_until_received<O>(&mut self, output_receiver: &Receiver<O>) -> Option<O> {
+        match output_receiver.recv_timeout(SHOW_AFTER) {
+            Ok(output) => return Some(output),
+            Err(RecvTimeoutError::Disconnected) => return None,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        loop {
+            self.draw();
+            while event::poll(Duration::ZERO).unwrap_or(false) {
+                if let Ok(Event::Key(key_event)) = event::read() {
+                    if key_event_to_key(&key_event).is_ok_and(|key| key == self.cancel_control.get_key()) {
+                        self.command_activity.request_cancel();
+                    }
+                }
+            }
+            match output_receiver.recv_timeout(TICK) {
+                Ok(output) => return Some(output),
+                Err(RecvTimeoutError::Disconnected) => return None,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        }
+    }
+
+    fn draw(&mut self) {
+        let Some(running_command) = self.command_activity.get_running_commands().into_iter().next() else {
+            return;
+        };
+        let frame = SPINNER_FRAMES[self.spinner_frame_index % SPINNER_FRAMES.len()];
+        self.spinner_frame_index += 1;
+        let text = get_indicator_text(frame, &running_command, &self.cancel_control, self.command_activity.is_cancel_requested());
+        _ = self.terminal.draw(|frame| {
+            // after a resize the last frame no longer fits, the next frame of the flow draws everything again
+            if self.last_frame.area == frame.area() {
+                *frame.buffer_mut() = self.last_frame.clone();
+            }
+            BusyIndicatorWidget::new(&text).render_ref(frame.area(), frame.buffer_mut());
+        });
+    }
+}
+
+fn get_indicator_text(frame: char, running_command: &RunningCommand, cancel_control: &Control, is_cancelling: bool) -> String {
+    let status = match is_cancelling {
+        true => "cancelling".to_string(),
+        false => format!("{}: {}", cancel_control.get_key(), cancel_control.get_name()),
+    };
+    format!(
+        "{frame} {:.1}s · {status} · {}",
+        running_command.started_at.elapsed().as_secs_f32(),
+        running_command.command
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_indicator_text, BusyCommandRunner};
+    use tuiflow_model_contracts::command_activity::CommandActivity;
+    use tuiflow_model_contracts::command_runner::{CommandOutput, CommandRunner, MockCommandRunner};
+    use tuiflow_model_contracts::control::{Control, Key};
+
+    #[test]
+    fn busy_command_runner_returns_outputs_of_commands_run_on_worker_in_order() {
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_command()
+            .times(3)
+            .returning(|command| Ok(CommandOutput::from_stdout(command.command.clone())));
+        let busy_command_runner = BusyCommandRunner { command_runner };
+
+        assert_eq!(busy_command_runner.run_command(&"ls".to_string().into()), Ok(CommandOutput::from_stdout("ls".to_string())));
+        let outputs = busy_command_runner.run_parallel_commands(&["du a".to_string().into(), "du b".to_string().into()]);
+        assert_eq!(
+            outputs,
+            vec![Ok(CommandOutput::from_stdout("du a".to_string())), Ok(CommandOutput::from_stdout("du b".to_string()))]
+        );
+    }
+
+    #[test]
+    fn indicator_text_shows_command_with_cancel_key_until_cancelling() {
+        let command_activity = CommandActivity::default();
+        let running_command_guard = command_activity.start("kubectl get pods");
+        let running_command = command_activity.get_running_commands().remove(0);
+        let cancel_control = Control::new("cancel", Key::Backspace);
+
+        assert_eq!(get_indicator_text('⠋', &running_command, &cancel_control, false), "⠋ 0.0s · Backspace: cancel · kubectl get pods");
+        assert_eq!(get_indicator_text('⠙', &running_command, &cancel_control, true), "⠙ 0.0s · cancelling · kubectl get pods");
+        drop(running_command_guard);
+        assert!(command_activity.get_running_commands().is_empty());
+    }
+}
@@ -5,18 +5,118 @@ use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct AppConfiguration {
+    pub version: u64, // see migration::CURRENT_FLOW_VERSION
     pub app_title: String,
     pub controls: ControlsConfiguration,
     pub initial_command: String,
     pub initial_state: String,
     pub initial_cli_output_variable_set_extractor: String,
+    #[serde(default)]
+    pub initial_on_empty: Option<OnEmptyConfiguration>,
+    #[serde(default)]
+    pub requires: Vec<RequirementConfiguration>,
     pub states: HashMap<String, StateConfiguration>,
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct RequirementConfiguration {
+    pub tool: String,
+    pub version_command: String,
+    #[serde(default = "RequirementConfiguration::default_version_pattern")]
+    pub version_pattern: String, // the named group `version` holds the dotted version number
+    #[serde(default)]
+    pub min_version: Option<String>,
+    #[serde(default)]
+    pub max_version: Option<String>,
+}
+
+impl RequirementConfiguration {
+    fn default_version_pattern() -> String {
+        String::from(r"(?<version>\d+(\.\d+)*)")
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct StateConfiguration {
+    #[serde(default)]
     pub transitions: Vec<TransitionConfiguration>,
+    #[serde(default)]
     pub line_display_pattern: String,
+    #[serde(default)]
+    pub kind: StateKindConfiguration,
+    #[serde(default)]
+    pub terminal: Option<TerminalConfiguration>,
+    #[serde(default)]
+    pub auto: Option<AutoTransitionConfiguration>,
+    #[serde(default)]
+    pub refresh_on_back: bool,
+    #[serde(default)]
+    pub stream: Option<StreamConfiguration>,
+    #[serde(default)]
+    pub refresh_interval_millis: Option<u64>,
+    #[serde(default)]
+    pub item_key_variable: Option<String>, // identifies the item of a line across refreshes, all variables if not set
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct StreamConfiguration {
+    #[serde(default = "StreamConfiguration::default_max_lines")]
+    pub max_lines: usize,
+    #[serde(default = "StreamConfiguration::default_follow")]
+    pub follow: bool,
+}
+
+impl StreamConfiguration {
+    fn default_max_lines() -> usize {
+        1000
+    }
+
+    fn default_follow() -> bool {
+        true
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct AutoTransitionConfiguration {
+    pub control_name: String,
+    #[serde(default)]
+    pub trigger: AutoTriggerConfiguration,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum AutoTriggerConfiguration {
+    #[default]
+    Always,
+    SingleLine,
+    DelayMillis(u64),
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct TerminalConfiguration {
+    pub control_name: String,
+    pub output_pattern: String,
+    #[serde(default)]
+    pub exit_code: u8,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum StateKindConfiguration {
+    #[default]
+    Lines,
+    Input {
+        prompt: String,
+        capture: InputCaptureConfiguration,
+    },
+    Prose {
+        #[serde(default)]
+        markdown: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub enum InputCaptureConfiguration {
+    Variable(String),
+    VariableSetExtractor(String),
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -24,14 +124,128 @@ pub struct TransitionConfiguration {
     pub control_name: String,
     pub cli_output_variable_set_extractor: String,
     pub command_pattern: String,
+    #[serde(default, alias = "on_success")]
+    pub next_state: Option<String>, // without one the transition stays in its state and refreshes it
+    #[serde(default)]
+    pub toast: bool, // shows the output of a transition without next state in the status bar
+    #[serde(default)]
+    pub on_failure: Option<String>, // the state to go to if a command exits with a non-zero code
+    #[serde(default)]
+    pub on_exit_code: HashMap<i32, String>, // the states to go to for specific exit codes, before on_failure
+    #[serde(default)]
+    pub on_output: Vec<OutputRouteConfiguration>, // tried in order before cli_output_variable_set_extractor
+    #[serde(default)]
+    pub on_empty: Option<OnEmptyConfiguration>,
+    #[serde(default)]
+    pub interactive: Option<InteractiveConfiguration>,
+    #[serde(default)]
+    pub guard: Option<String>, // deprecated, the same as `when: !LineMatches <regex>` and rewritten into it by migration
+    #[serde(default)]
+    pub when: Option<ConditionConfiguration>, // a condition on the variables of the selected line
+    #[serde(default)]
+    pub batch: BatchConfiguration,
+    #[serde(default)]
+    pub confirm: Option<ConfirmConfiguration>,
+    #[serde(default)]
+    pub fail_on_non_zero_exit: bool, // otherwise only the output of a command decides what comes next
+    #[serde(default)]
+    pub timeout_millis: Option<u64>, // kills the command once it runs for longer
+}
+
+/// A condition on the variables of a line, combined with `And` and `Or`.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub enum ConditionConfiguration {
+    Equals { variable: String, value: String },
+    Matches { variable: String, regex: String },
+    LineMatches(String), // the regex matches the whole selected line, or the typed text of an input state
+    Less { variable: String, than: f64 },
+    LessOrEqual { variable: String, than: f64 },
+    Greater { variable: String, than: f64 },
+    GreaterOrEqual { variable: String, than: f64 },
+    Exists(String),
+    NotExists(String),
+    And(Vec<ConditionConfiguration>),
+    Or(Vec<ConditionConfiguration>),
+}
+
+/// A state the transition leads to instead of its next state if the extractor matches the output.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct OutputRouteConfiguration {
+    pub cli_output_variable_set_extractor: String,
     pub next_state: String,
 }
 
+/// A question the user answers with yes before the transition runs.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ConfirmConfiguration {
+    pub message: String, // filled with the variables of the selected or marked lines
+}
+
+/// How the transition runs when lines are marked.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub enum BatchConfiguration {
+    Each {
+        #[serde(default = "BatchConfiguration::default_parallel")]
+        parallel: usize,
+    },
+    Joined {
+        #[serde(default = "BatchConfiguration::default_separator")]
+        separator: String,
+    },
+}
+
+impl Default for BatchConfiguration {
+    fn default() -> Self {
+        Self::Each {
+            parallel: Self::default_parallel(),
+        }
+    }
+}
+
+impl BatchConfiguration {
+    fn default_parallel() -> usize {
+        1
+    }
+
+    fn default_separator() -> String {
+        " ".to_string()
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub enum OnEmptyConfiguration {
+    Command(String),
+    Lines(Vec<String>),
+    Refuse(String),
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub enum InteractiveConfiguration {
+    Return(String),
+    Exit,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct ControlsConfiguration {
     pub selection_up: Control,
     pub selection_down: Control,
     pub quit: Control,
+    #[serde(default = "ControlsConfiguration::default_filter")]
+    pub filter: Control,
+    #[serde(default = "ControlsConfiguration::default_back")]
+    pub back: Control,
+    #[serde(default = "ControlsConfiguration::default_toggle_mark")]
+    pub toggle_mark: Control,
+    #[serde(default = "ControlsConfiguration::default_mark_all")]
+    pub mark_all: Control,
+    #[serde(default = "ControlsConfiguration::default_invert_marks")]
+    pub invert_marks: Control,
+    #[serde(default = "ControlsConfiguration::default_cancel")]
+    pub cancel: Control,
+    #[serde(default = "ControlsConfiguration::default_error_log")]
+    pub error_log: Control,
+    #[serde(default = "ControlsConfiguration::default_refresh")]
+    pub refresh: Control,
     pub custom_controls: HashMap<String, Control>,
 }
 
@@ -41,7 +255,56 @@ impl Default for ControlsConfiguration {
             selection_up: Control::new("selection up", Key::Char('k')),
             selection_down: Control::new("selection down", Key::Char('j')),
             quit: Control::new("quit", Key::Char('q')),
+            filter: Self::default_filter(),
+            back: Self::default_back(),
+            toggle_mark: Self::default_toggle_mark(),
+            mark_all: Self::default_mark_all(),
+            invert_marks: Self::default_invert_marks(),
+            cancel: Self::default_cancel(),
+            error_log: Self::default_error_log(),
+            refresh: Self::default_refresh(),
             custom_controls: HashMap::new(),
         }
     }
-}
\ No newline at end of file
+}
+
+impl ControlsConfiguration {
+    fn default_filter() -> Control {
+        Control::new("filter", Key::Char('/'))
+    }
+
+    fn default_back() -> Control {
+        Control::new("back", Key::Esc)
+    }
+
+    fn default_toggle_mark() -> Control {
+        Control::new("mark", Key::Tab)
+    }
+
+    fn default_mark_all() -> Control {
+        Control::new("mark all", Key::Char('*'))
+    }
+
+    fn default_invert_marks() -> Control {
+        Control::new("invert marks", Key::Char('!'))
+    }
+
+    fn default_cancel() -> Control {
+        Control::new("cancel", Key::Backspace)
+    }
+
+    fn default_error_log() -> Control {
+        Control::new("error log", Key::Char('E'))
+    }
+
+    fn default_refresh() -> Control {
+        Control::new("refresh", Key::Char('R'))
+    }
+}
+
+/// Values handed to tuiflow on the command line or through stdin instead of the flow file.
+#[derive(PartialEq, Debug, Default)]
+pub struct LaunchArguments {
+    pub variables: Vec<(String, String)>, // usable in the initial command
+    pub stdin: Option<String>,            // replaces the output of the initial command, None if nothing was piped
+}
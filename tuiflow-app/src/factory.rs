@@ -1,39 +1,59 @@
-use crate::configuration::AppConfiguration;
+use crate::configuration::{AppConfiguration, AutoTriggerConfiguration, BatchConfiguration, ConditionConfiguration, ControlsConfiguration, LaunchArguments, InputCaptureConfiguration, InteractiveConfiguration, OnEmptyConfiguration, StateConfiguration, StateKindConfiguration, TransitionConfiguration};
+use crate::requirements;
 use eyre::OptionExt;
+use regex::Regex;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-use tuiflow_model::state::{Transit, WorkflowState};
-use tuiflow_model::variable_mapping::{RegexVariableExtractor, VariableInjector};
+use std::time::Duration;
+use tuiflow_model::state::{AutoTransition, AutoTrigger, InputCapture, State, StreamSettings, TerminalExit, Transit, WorkflowState, WorkflowStateKind};
+use tuiflow_model::variable::{Variable, VariableSet};
+use tuiflow_model::variable_mapping::{Comparison, RegexVariableExtractor, VariableCondition, VariableExtractor, VariableInjector};
 use tuiflow_model::workflow::Workflow;
+use tuiflow_model::transition::{BatchMode, EmptyOutputFallback, ExitRoute, InteractiveMode};
 use tuiflow_model::{transition, Control};
 use tuiflow_model_contracts::command_runner::CommandRunner;
 use tuiflow_model_contracts::control::Key;
+use tuiflow_model_contracts::error::{InitialTransitionError, StateTransitionError};
+
+const MIN_REFRESH_INTERVAL_MILLIS: u64 = 250; // shorter intervals would keep the flow busy refreshing
 
 pub trait ConstructWorkflow<T: Transit> {
     fn build_from_configuration(
-        app_config: AppConfiguration
+        app_config: &AppConfiguration,
+        launch_arguments: &LaunchArguments,
     ) -> eyre::Result<Workflow<T>>;
 }
 
+type SharedWorkflowState<R> = Rc<RefCell<WorkflowState<transition::Transition<R, RegexVariableExtractor>>>>;
+
 pub struct WorkflowFactory<R: CommandRunner> {
     _phantom: std::marker::PhantomData<R>,
 }
 
 impl<R: CommandRunner> ConstructWorkflow<transition::Transition<R, RegexVariableExtractor>> for WorkflowFactory<R> {
     fn build_from_configuration (
-        app_config: AppConfiguration,
+        app_config: &AppConfiguration,
+        launch_arguments: &LaunchArguments,
     ) -> eyre::Result<Workflow<transition::Transition<R, RegexVariableExtractor>>> {
-        let states: HashMap<String, Rc<RefCell<WorkflowState<transition::Transition<R, RegexVariableExtractor>>>>> =
+        Self::check_reserved_keys(&app_config.controls)?;
+        requirements::check_requirements(&R::new(), &app_config.requires)?;
+
+        let states: HashMap<String, SharedWorkflowState<R>> =
             app_config
                 .states
                 .iter()
-                .map(|(name, config)| (name.clone(), Self::build_state(config.line_display_pattern.as_str(), name)))
-                .collect();
+                .map(|(name, config)| {
+                    Ok((name.clone(), Self::build_state(config, name, &app_config.controls.custom_controls)?))
+                })
+                .collect::<eyre::Result<_>>()?;
 
         for (name, state) in states.iter() {
             let state_config = app_config.states.get(name).unwrap(); //safe unwrap
             for transition_config in &state_config.transitions {
+                Self::check_timeout(transition_config, name, &app_config.states)?;
+                Self::check_exit_routes(transition_config, name, &app_config.states)?;
+                Self::check_toast(transition_config, name)?;
                 let transition_control = app_config
                     .controls
                     .custom_controls
@@ -45,19 +65,42 @@ impl<R: CommandRunner> ConstructWorkflow<transition::Transition<R, RegexVariable
 
                 let variable_extractor =
                     RegexVariableExtractor::new(transition_config.cli_output_variable_set_extractor.as_str())?;
-                let transition = transition::Transition::new(
-                    transition_control.clone(),
-                    states
-                        .get(transition_config.next_state.as_str())
-                        .ok_or_eyre(format!(
-                            "Next state {} named in transition config not found",
-                            transition_config.next_state
-                        ))?
-                        .clone(),
-                    VariableInjector::new(transition_config.command_pattern.clone()),
-                    R::new(),
-                    variable_extractor,
+                let transition = Self::with_on_empty(
+                    transition::Transition::new(
+                        transition_control.clone(),
+                        match &transition_config.next_state {
+                            Some(next_state) => Self::get_next_state(&states, next_state)?,
+                            None => Rc::clone(state),
+                        },
+                        VariableInjector::new(transition_config.command_pattern.clone()),
+                        match transition_config.timeout_millis {
+                            Some(timeout_millis) => R::new().with_timeout(Duration::from_millis(timeout_millis)),
+                            None => R::new(),
+                        },
+                        variable_extractor,
+                    ),
+                    &transition_config.on_empty,
                 );
+                let transition = Self::with_interactive(transition, &transition_config.interactive);
+                let transition = match Self::build_transition_condition(transition_config)? {
+                    Some(condition) => transition.with_condition(condition),
+                    None => transition,
+                };
+                let transition = transition.with_batch_mode(match &transition_config.batch {
+                    BatchConfiguration::Each { parallel } => BatchMode::Each { parallelism: *parallel },
+                    BatchConfiguration::Joined { separator } => BatchMode::Joined { separator: separator.clone() },
+                });
+                let transition = match &transition_config.confirm {
+                    Some(confirm) => transition.with_confirmation(VariableInjector::new(confirm.message.clone())),
+                    None => transition,
+                };
+                let transition = transition.with_fail_on_non_zero_exit(transition_config.fail_on_non_zero_exit);
+                let transition = match transition_config.next_state {
+                    Some(_) => transition,
+                    None => transition.with_side_effect(transition_config.toast),
+                };
+                let transition = Self::with_exit_routes(transition, transition_config, &states)?;
+                let transition = Self::with_output_routes(transition, transition_config, &states)?;
                 state
                     .borrow_mut()
                     .add_transition(transition_control.get_key(), transition);
@@ -72,31 +115,415 @@ impl<R: CommandRunner> ConstructWorkflow<transition::Transition<R, RegexVariable
             ))?
             .clone();
 
-        let initial_transition = transition::Transition::new(
-            Control::new("INIT", Key::Backspace),
-            initial_state.clone(),
-            VariableInjector::new(app_config.initial_command.clone()),
-            R::new(),
-            RegexVariableExtractor::new(app_config.initial_cli_output_variable_set_extractor.as_str())?,
+        let initial_variable_extractor =
+            RegexVariableExtractor::new(app_config.initial_cli_output_variable_set_extractor.as_str())?;
+        let initial_variables: VariableSet = launch_arguments
+            .variables
+            .iter()
+            .map(|(name, value)| Variable::new(name.clone().into(), value.clone()))
+            .collect();
+        if let Some(stdin) = &launch_arguments.stdin {
+            // the launch variables stay usable in the commands of the lines, where the variables of a line win
+            let current_state = if initial_state.borrow().is_prose() {
+                State::new_prose(initial_state, stdin, initial_variables)
+            } else {
+                let lines = initial_variable_extractor
+                    .extract(stdin)
+                    .iter()
+                    .map(|line_variables| initial_variables.merged_with(line_variables))
+                    .collect();
+                State::new(initial_state, lines)
+            };
+            return Ok(Workflow::from_state(current_state, app_config.app_title.clone()));
+        }
+
+        let initial_transition = Self::with_on_empty(
+            transition::Transition::new(
+                Control::new("INIT", Key::Backspace),
+                initial_state.clone(),
+                VariableInjector::new(app_config.initial_command.clone()),
+                R::new(),
+                initial_variable_extractor,
+            ),
+            &app_config.initial_on_empty,
         );
         let initializer_state = WorkflowState::new(
             "INIT",
             VariableInjector::new("".to_string()),
             vec![initial_transition],
         );
-        let workflow = Workflow::new(initializer_state, app_config.app_title)?;
-        Ok(workflow)
+        match Workflow::new(initializer_state, app_config.app_title.clone(), initial_variables) {
+            Ok(workflow) => Ok(workflow),
+            Err(e) => match e.into_transition_error() {
+                // a refusing on_empty still opens the flow, showing its message next to the empty initial state
+                refusal @ StateTransitionError::EmptyOutput(_) => {
+                    Ok(Workflow::from_state(State::new(initial_state, vec![]), app_config.app_title.clone())
+                        .with_background_error(refusal))
+                }
+                e => Err(InitialTransitionError::from(e).into()),
+            },
+        }
     }
 
 }
 
 impl<R: CommandRunner> WorkflowFactory<R> {
+    /// Fails if a custom control has the key of a reserved control that is handled first, so it could never run.
+    fn check_reserved_keys(controls: &ControlsConfiguration) -> eyre::Result<()> {
+        let reserved_controls = [
+            &controls.filter,
+            &controls.back,
+            &controls.toggle_mark,
+            &controls.mark_all,
+            &controls.invert_marks,
+            &controls.error_log,
+            &controls.refresh,
+        ];
+        for (name, custom_control) in &controls.custom_controls {
+            let key = custom_control.get_key();
+            if let Some(reserved_control) = reserved_controls
+                .iter()
+                .find(|reserved_control| reserved_control.get_key() == key)
+            {
+                eyre::bail!(
+                    "Custom control {name} has the key {key} of the reserved control {}",
+                    reserved_control.get_name()
+                );
+            }
+        }
+        Ok(())
+    }
+
     fn build_state(
-        line_display_pattern: &str,
+        state_config: &StateConfiguration,
+        name: &str,
+        custom_controls: &HashMap<String, Control>,
+    ) -> eyre::Result<SharedWorkflowState<R>> {
+        Self::check_input_capture(state_config, name)?;
+        Self::check_auto_transition(state_config, name)?;
+        Self::check_refresh_interval(state_config, name)?;
+        let variable_mapper = VariableInjector::new(state_config.line_display_pattern.clone());
+        let mut state = WorkflowState::<transition::Transition<R, RegexVariableExtractor>>::new(name, variable_mapper, vec![])
+            .with_kind(Self::build_state_kind(&state_config.kind)?)
+            .with_refresh_on_back(state_config.refresh_on_back);
+        if let Some(terminal_config) = &state_config.terminal {
+            let exit_control = custom_controls
+                .get(&terminal_config.control_name)
+                .ok_or_eyre(format!(
+                    "Control {} named in terminal config of state {} not found",
+                    terminal_config.control_name, name
+                ))?;
+            state = state.with_terminal_exit(TerminalExit::new(
+                exit_control.clone(),
+                VariableInjector::new(terminal_config.output_pattern.clone()),
+                terminal_config.exit_code,
+            ));
+        }
+        if let Some(auto_config) = &state_config.auto {
+            let auto_control = custom_controls
+                .get(&auto_config.control_name)
+                .ok_or_eyre(format!(
+                    "Control {} named in auto config of state {} not found",
+                    auto_config.control_name, name
+                ))?;
+            let trigger = match auto_config.trigger {
+                AutoTriggerConfiguration::Always => AutoTrigger::Always,
+                AutoTriggerConfiguration::SingleLine => AutoTrigger::SingleLine,
+                AutoTriggerConfiguration::DelayMillis(millis) => AutoTrigger::Delay(Duration::from_millis(millis)),
+            };
+            state = state.with_auto_transition(AutoTransition::new(auto_control.get_key(), trigger));
+        }
+        if let Some(stream_config) = &state_config.stream {
+            state = state.with_stream(StreamSettings {
+                max_lines: stream_config.max_lines,
+                follow: stream_config.follow,
+            });
+        }
+        if let Some(refresh_interval_millis) = state_config.refresh_interval_millis {
+            state = state.with_refresh_interval(Duration::from_millis(refresh_interval_millis));
+        }
+        if let Some(item_key_variable) = &state_config.item_key_variable {
+            state = state.with_item_key_variable(item_key_variable.clone());
+        }
+        Ok(Rc::new(RefCell::new(state)))
+    }
+
+    /// Fails if the variable an input state captures is used by none of its commands, e.g. because of a typo.
+    fn check_input_capture(state_config: &StateConfiguration, name: &str) -> eyre::Result<()> {
+        let StateKindConfiguration::Input { capture: InputCaptureConfiguration::Variable(variable_name), .. } = &state_config.kind else {
+            return Ok(());
+        };
+        let patterns = state_config
+            .transitions
+            .iter()
+            .map(|transition_config| transition_config.command_pattern.as_str())
+            .chain(state_config.terminal.iter().map(|terminal_config| terminal_config.output_pattern.as_str()))
+            .collect::<Vec<_>>();
+        let placeholder = format!("<{variable_name}>");
+        if !patterns.is_empty() && !patterns.iter().any(|pattern| pattern.contains(&placeholder)) {
+            eyre::bail!("Variable {variable_name} captured by input state {name} is used by none of its command patterns");
+        }
+        Ok(())
+    }
+
+    /// Fails if the auto transition would hand the terminal over to an interactive program, which needs a key press.
+    fn check_auto_transition(state_config: &StateConfiguration, name: &str) -> eyre::Result<()> {
+        let Some(auto_config) = &state_config.auto else {
+            return Ok(());
+        };
+        let is_interactive = state_config
+            .transitions
+            .iter()
+            .any(|transition_config| {
+                transition_config.control_name == auto_config.control_name && transition_config.interactive.is_some()
+            });
+        if is_interactive {
+            eyre::bail!(
+                "Control {} of the auto transition of state {} runs an interactive program, which needs a key press",
+                auto_config.control_name, name
+            );
+        }
+        Ok(())
+    }
+
+    fn check_refresh_interval(state_config: &StateConfiguration, name: &str) -> eyre::Result<()> {
+        match state_config.refresh_interval_millis {
+            Some(refresh_interval_millis) if refresh_interval_millis < MIN_REFRESH_INTERVAL_MILLIS => eyre::bail!(
+                "Refresh interval of state {name} is {refresh_interval_millis} ms, it must be at least {MIN_REFRESH_INTERVAL_MILLIS} ms"
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if the transition has a timeout its commands can't run into, a program attached to the terminal
+    /// or a stream runs until the user ends it.
+    fn check_timeout(
+        transition_config: &TransitionConfiguration,
+        name: &str,
+        state_configs: &HashMap<String, StateConfiguration>,
+    ) -> eyre::Result<()> {
+        if transition_config.timeout_millis.is_none() {
+            return Ok(());
+        }
+        if transition_config.interactive.is_some() {
+            eyre::bail!(
+                "Control {} of state {name} runs an interactive program, which can't have a timeout",
+                transition_config.control_name
+            );
+        }
+        if Self::is_streaming(transition_config, state_configs) {
+            eyre::bail!(
+                "Control {} of state {name} starts a stream, which can't have a timeout",
+                transition_config.control_name
+            );
+        }
+        Ok(())
+    }
+
+    /// Fails if a transition with a next state asks for a toast.
+    fn check_toast(transition_config: &TransitionConfiguration, name: &str) -> eyre::Result<()> {
+        if transition_config.toast && transition_config.next_state.is_some() {
+            eyre::bail!(
+                "Control {} of state {name} has a toast and a next state, only transitions without next state show a toast",
+                transition_config.control_name
+            );
+        }
+        Ok(())
+    }
+
+    /// Fails if the transition routes exit codes its commands never report, the exit of a program attached
+    /// to the terminal or of a stream is not awaited.
+    fn check_exit_routes(
+        transition_config: &TransitionConfiguration,
         name: &str,
-    ) -> Rc<RefCell<WorkflowState<transition::Transition<R, RegexVariableExtractor>>>> {
-        let variable_mapper = VariableInjector::new(line_display_pattern.to_string());
-        let state = WorkflowState::<transition::Transition<R, RegexVariableExtractor>>::new(name, variable_mapper, vec![]);
-        Rc::new(RefCell::new(state))
+        state_configs: &HashMap<String, StateConfiguration>,
+    ) -> eyre::Result<()> {
+        if transition_config.on_failure.is_none() && transition_config.on_exit_code.is_empty() {
+            return Ok(());
+        }
+        if transition_config.interactive.is_some() {
+            eyre::bail!(
+                "Control {} of state {name} runs an interactive program, which can't route its exit codes",
+                transition_config.control_name
+            );
+        }
+        if Self::is_streaming(transition_config, state_configs) {
+            eyre::bail!(
+                "Control {} of state {name} starts a stream, which can't route its exit codes",
+                transition_config.control_name
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether the transition keeps its command running to stream its output into the next state.
+    fn is_streaming(transition_config: &TransitionConfiguration, state_configs: &HashMap<String, StateConfiguration>) -> bool {
+        transition_config
+            .next_state
+            .as_ref()
+            .and_then(|next_state| state_configs.get(next_state))
+            .is_some_and(|next_state_config| next_state_config.stream.is_some())
+    }
+
+    fn get_next_state(states: &HashMap<String, SharedWorkflowState<R>>, name: &str) -> eyre::Result<SharedWorkflowState<R>> {
+        states
+            .get(name)
+            .cloned()
+            .ok_or_eyre(format!("Next state {name} named in transition config not found"))
     }
-}
\ No newline at end of file
+
+    fn with_exit_routes(
+        transition: transition::Transition<R, RegexVariableExtractor>,
+        transition_config: &TransitionConfiguration,
+        states: &HashMap<String, SharedWorkflowState<R>>,
+    ) -> eyre::Result<transition::Transition<R, RegexVariableExtractor>> {
+        let mut transition = transition;
+        for (exit_code, state_name) in &transition_config.on_exit_code {
+            transition = transition.with_exit_route(ExitRoute::Code(*exit_code), Self::get_next_state(states, state_name)?);
+        }
+        if let Some(state_name) = &transition_config.on_failure {
+            transition = transition.with_exit_route(ExitRoute::Failure, Self::get_next_state(states, state_name)?);
+        }
+        Ok(transition)
+    }
+
+    fn with_output_routes(
+        transition: transition::Transition<R, RegexVariableExtractor>,
+        transition_config: &TransitionConfiguration,
+        states: &HashMap<String, SharedWorkflowState<R>>,
+    ) -> eyre::Result<transition::Transition<R, RegexVariableExtractor>> {
+        let mut transition = transition;
+        for output_route_config in &transition_config.on_output {
+            transition = transition.with_output_route(
+                RegexVariableExtractor::new(output_route_config.cli_output_variable_set_extractor.as_str())?,
+                Self::get_next_state(states, &output_route_config.next_state)?,
+            );
+        }
+        Ok(transition)
+    }
+
+    /// The `when` condition of the transition, together with the deprecated `guard` on the line if it has one.
+    fn build_transition_condition(transition_config: &TransitionConfiguration) -> eyre::Result<Option<VariableCondition>> {
+        let guard = match &transition_config.guard {
+            Some(guard) => Some(VariableCondition::LineMatches(Regex::new(guard)?)),
+            None => None,
+        };
+        let when = match &transition_config.when {
+            Some(condition_config) => Some(Self::build_condition(condition_config)?),
+            None => None,
+        };
+        Ok(match (guard, when) {
+            (Some(guard), Some(when)) => Some(VariableCondition::And(vec![guard, when])),
+            (guard, when) => guard.or(when),
+        })
+    }
+
+    fn build_condition(condition_config: &ConditionConfiguration) -> eyre::Result<VariableCondition> {
+        let compare = |variable: &str, comparison, number| VariableCondition::Compare {
+            variable: variable.into(),
+            comparison,
+            number,
+        };
+        let build_all = |condition_configs: &[ConditionConfiguration]| {
+            condition_configs
+                .iter()
+                .map(Self::build_condition)
+                .collect::<eyre::Result<Vec<_>>>()
+        };
+        Ok(match condition_config {
+            ConditionConfiguration::Equals { variable, value } => VariableCondition::Equals {
+                variable: variable.as_str().into(),
+                value: value.clone(),
+            },
+            ConditionConfiguration::Matches { variable, regex } => VariableCondition::Matches {
+                variable: variable.as_str().into(),
+                regex: Regex::new(regex)?,
+            },
+            ConditionConfiguration::LineMatches(regex) => VariableCondition::LineMatches(Regex::new(regex)?),
+            ConditionConfiguration::Less { variable, than } => compare(variable, Comparison::Less, *than),
+            ConditionConfiguration::LessOrEqual { variable, than } => compare(variable, Comparison::LessOrEqual, *than),
+            ConditionConfiguration::Greater { variable, than } => compare(variable, Comparison::Greater, *than),
+            ConditionConfiguration::GreaterOrEqual { variable, than } => {
+                compare(variable, Comparison::GreaterOrEqual, *than)
+            }
+            ConditionConfiguration::Exists(variable) => VariableCondition::Exists(variable.as_str().into()),
+            ConditionConfiguration::NotExists(variable) => VariableCondition::NotExists(variable.as_str().into()),
+            ConditionConfiguration::And(condition_configs) => VariableCondition::And(build_all(condition_configs)?),
+            ConditionConfiguration::Or(condition_configs) => VariableCondition::Or(build_all(condition_configs)?),
+        })
+    }
+
+    fn with_on_empty(
+        transition: transition::Transition<R, RegexVariableExtractor>,
+        on_empty_config: &Option<OnEmptyConfiguration>,
+    ) -> transition::Transition<R, RegexVariableExtractor> {
+        let Some(on_empty_config) = on_empty_config else {
+            return transition;
+        };
+        let on_empty = match on_empty_config {
+            OnEmptyConfiguration::Command(command_pattern) => {
+                EmptyOutputFallback::Command(VariableInjector::new(command_pattern.clone()))
+            }
+            OnEmptyConfiguration::Lines(lines) => EmptyOutputFallback::Lines(lines.join("\n")),
+            OnEmptyConfiguration::Refuse(message_pattern) => {
+                EmptyOutputFallback::Refuse(VariableInjector::new(message_pattern.clone()))
+            }
+        };
+        transition.with_on_empty(on_empty)
+    }
+
+    fn with_interactive(
+        transition: transition::Transition<R, RegexVariableExtractor>,
+        interactive_config: &Option<InteractiveConfiguration>,
+    ) -> transition::Transition<R, RegexVariableExtractor> {
+        match interactive_config {
+            None => transition,
+            Some(InteractiveConfiguration::Return(resume_command_pattern)) => transition.with_interactive(
+                InteractiveMode::Return(VariableInjector::new(resume_command_pattern.clone())),
+            ),
+            Some(InteractiveConfiguration::Exit) => transition.with_interactive(InteractiveMode::Exit),
+        }
+    }
+
+    fn build_state_kind(kind_config: &StateKindConfiguration) -> eyre::Result<WorkflowStateKind> {
+        let kind = match kind_config {
+            StateKindConfiguration::Lines => WorkflowStateKind::Lines,
+            StateKindConfiguration::Input { prompt, capture } => {
+                let capture = match capture {
+                    InputCaptureConfiguration::Variable(variable_name) => {
+                        InputCapture::Variable(variable_name.as_str().into())
+                    }
+                    InputCaptureConfiguration::VariableSetExtractor(extractor) => {
+                        InputCapture::VariableSetExtractor(RegexVariableExtractor::new(extractor.as_str())?)
+                    }
+                };
+                WorkflowStateKind::Input {
+                    prompt: prompt.clone(),
+                    capture,
+                }
+            }
+            StateKindConfiguration::Prose { markdown } => WorkflowStateKind::Prose {
+                markdown: *markdown,
+            },
+        };
+        Ok(kind)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WorkflowFactory;
+    use crate::configuration::ControlsConfiguration;
+    use tuiflow_model::Control;
+    use tuiflow_model_contracts::command_runner::MockCommandRunner;
+    use tuiflow_model_contracts::control::Key;
+
+    #[test]
+    fn check_reserved_keys_rejects_custom_control_with_key_of_filter() {
+        let mut controls = ControlsConfiguration::default();
+        controls.custom_controls.insert("search".to_string(), Control::new("search", Key::Char('/')));
+
+        let message = WorkflowFactory::<MockCommandRunner>::check_reserved_keys(&controls).unwrap_err().to_string();
+        assert_eq!(message, "Custom control search has the key / of the reserved control filter");
+    }
+}
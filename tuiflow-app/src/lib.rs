@@ -1,63 +1,196 @@
-use crate::configuration::AppConfiguration;
+use crate::configuration::{AppConfiguration, LaunchArguments};
 use crate::factory::ConstructWorkflow;
 use crate::state::AppState;
 use crossterm::event;
 use crossterm::event::Event;
-use ratatui::widgets::StatefulWidgetRef;
-use ratatui::{DefaultTerminal, Frame};
+use ratatui::buffer::Buffer;
+use ratatui::widgets::{StatefulWidgetRef, WidgetRef};
+use ratatui::Frame;
 use std::time::Duration;
 use tuiflow_model::state::Transit;
 use tuiflow_model::workflow::Workflow;
 use tuiflow_model::Control;
+use tuiflow_model_contracts::command_activity::CommandActivity;
 use tuiflow_model_contracts::control::Key;
+use tuiflow_model_contracts::terminal_flow::{FlowExit, TerminalFlow, TerminalHandover};
+use terminal::TtyTerminal;
+use tuiflow_ui::error_widget::StartupErrorWidget;
 use tuiflow_ui::io;
-use tuiflow_ui::io::InputUpdatedViewModel;
+use tuiflow_ui::io::{InputUpdatedViewModel, KeyRouting};
 use tuiflow_ui::main_widget::{MainState, MainViewModel, MainWidget};
+use tuiflow_ui::reserved_controls::ReservedControls;
 
+pub mod busy_indicator;
 pub mod configuration;
 pub mod factory;
+pub mod migration;
+mod requirements;
 mod state;
+mod terminal;
+
+const RETRY_KEY: Key = Key::Char('r');
 
 pub struct App<T: Transit, F: ConstructWorkflow<T>> {
     app_state: AppState,
-    up_control: Control,
-    down_control: Control,
-    workflow: Workflow<T>,
-    _phantom: std::marker::PhantomData<F>,
+    reserved_controls: ReservedControls,
+    cancel_control: Control,
+    command_activity: CommandActivity, // the commands of the flow, to show and cancel while they run
+    configuration: AppConfiguration,
+    launch_arguments: LaunchArguments,
+    _phantom: std::marker::PhantomData<(T, F)>,
 }
 
 impl<T: Transit, F: ConstructWorkflow<T>> App<T, F> {
-    pub fn new(configuration: AppConfiguration) -> eyre::Result<Self> {
+    pub fn new(
+        configuration: AppConfiguration,
+        launch_arguments: LaunchArguments,
+        command_activity: CommandActivity,
+    ) -> eyre::Result<Self> {
         let quit_control = configuration.controls.quit.clone();
-        let up_control = configuration.controls.selection_up.clone();
-        let down_control = configuration.controls.selection_down.clone();
-        let workflow = F::build_from_configuration(configuration)?;
+        let controls = &configuration.controls;
+        let reserved_controls = ReservedControls {
+            selection_up: controls.selection_up.clone(),
+            selection_down: controls.selection_down.clone(),
+            filter: controls.filter.clone(),
+            back: controls.back.clone(),
+            toggle_mark: controls.toggle_mark.clone(),
+            mark_all: controls.mark_all.clone(),
+            invert_marks: controls.invert_marks.clone(),
+            error_log: controls.error_log.clone(),
+            refresh: controls.refresh.clone(),
+        };
+        let cancel_control = controls.cancel.clone();
+        // a cancel pressed just after the command ended would go back instead
+        if cancel_control.get_key() == controls.back.get_key() {
+            eyre::bail!("The cancel control can't have the key {} of the back control", cancel_control.get_key());
+        }
         Ok(Self {
             app_state: AppState::Running { quit_control },
-            workflow,
-            up_control,
-            down_control,
+            reserved_controls,
+            cancel_control,
+            command_activity,
+            configuration,
+            launch_arguments,
             _phantom: std::marker::PhantomData,
         })
     }
 
-    pub fn run(mut self, mut terminal: DefaultTerminal) -> eyre::Result<()> {
-        let mut view_model = MainViewModel::new(
-            &self.workflow,
-            self.up_control.clone(),
-            self.down_control.clone(),
-        );
+    /// Takes over the terminal until the flow is quit, returning the exit of the flow if a terminal state ended it.
+    /// The flow is built once the terminal is taken over, so the initial command already shows as running.
+    pub fn run(mut self) -> eyre::Result<Option<FlowExit>> {
+        let cancel_control = self.cancel_control.clone();
+        let command_activity = self.command_activity.clone();
+        let mut terminal = terminal::init()?;
+        let mut last_frame = terminal.draw(|_| {})?.buffer.clone();
+        let mut workflow;
+        (workflow, terminal) = self.build_workflow(terminal, last_frame.clone()).inspect_err(|_| terminal::restore())?;
+        terminal.clear()?;
+
+        let mut view_model = MainViewModel::new(&workflow, self.reserved_controls.clone());
+        view_model.report_background_errors(&workflow.take_background_errors());
         let mut main_widget = MainWidget::new(&view_model);
         let mut main_state = MainState::new();
 
         while self.app_state.is_running() {
-            if let Some(key) = self.should_update(&view_model, &main_state)? {
-                self.update(&mut view_model, &mut main_state, &key);
+            if let Some(key) = self.should_update(&view_model, &main_state, &workflow)? {
+                match view_model.get_terminal_handover(&main_state, &workflow, &key) {
+                    Some(terminal_handover) => {
+                        terminal::restore();
+                        let handover_result = view_model.hand_over(&mut main_state, &mut workflow, &key);
+                        match terminal_handover {
+                            // e.g. the program could not be started, which must not end the flow silently
+                            TerminalHandover::Exit => return handover_result.map(|_| None).map_err(eyre::Report::from),
+                            // the failure is shown in the flow
+                            TerminalHandover::Return => terminal = terminal::init()?,
+                        }
+                    }
+                    None if view_model.may_run_command(&main_state, &workflow, &key) => {
+                        ((), terminal) = busy_indicator::while_busy(terminal, &last_frame, &command_activity, &cancel_control, || {
+                            view_model.update(&mut main_state, &mut workflow, &key)
+                        });
+                    }
+                    None => view_model.update(&mut main_state, &mut workflow, &key),
+                }
                 main_widget = MainWidget::new(&view_model)
+            } else if !view_model.is_dialog_open(&main_state) {
+                let (has_transitioned, has_refreshed) = match workflow.is_background_work_due() {
+                    true => {
+                        let has_changed;
+                        (has_changed, terminal) = busy_indicator::while_busy(terminal, &last_frame, &command_activity, &cancel_control, || {
+                            let has_transitioned = workflow.run_due_auto_transition();
+                            (has_transitioned, !has_transitioned && workflow.run_due_refresh())
+                        });
+                        has_changed
+                    }
+                    false => (false, false),
+                };
+                let background_errors = workflow.take_background_errors();
+                if !background_errors.is_empty() {
+                    view_model.report_background_errors(&background_errors);
+                    main_widget = MainWidget::new(&view_model)
+                }
+                if has_refreshed {
+                    view_model.refresh_lines(&mut main_state, &workflow);
+                    main_widget = MainWidget::new(&view_model)
+                } else if has_transitioned {
+                    view_model.refresh(&mut main_state, &workflow);
+                    main_widget = MainWidget::new(&view_model)
+                } else if let Some(stream_update) = workflow.poll_stream() {
+                    view_model.append_streamed_lines(&mut main_state, &workflow, stream_update);
+                    main_widget = MainWidget::new(&view_model)
+                }
+            }
+            // a control or an auto transition ended the flow
+            if let Some(exit) = workflow.get_exit() {
+                terminal::restore();
+                return Ok(Some(exit.clone()));
+            }
+            // kept to show while the flow waits for a command
+            if let Ok(completed_frame) = terminal.draw(|frame| self.draw(frame, &main_widget, &mut main_state)) {
+                last_frame = completed_frame.buffer.clone();
+            }
+        }
+        terminal::restore();
+        Ok(None)
+    }
+
+    /// Builds the flow while showing it as running. A failure is shown until the user retries or quits,
+    /// quitting returns the error.
+    fn build_workflow(&mut self, mut terminal: TtyTerminal, mut last_frame: Buffer) -> eyre::Result<(Workflow<T>, TtyTerminal)> {
+        loop {
+            let workflow;
+            (workflow, terminal) = busy_indicator::while_busy(terminal, &last_frame, &self.command_activity, &self.cancel_control, || {
+                F::build_from_configuration(&self.configuration, &self.launch_arguments)
+            });
+            let error = match workflow {
+                Ok(workflow) => return Ok((workflow, terminal)),
+                Err(error) => error,
+            };
+
+            let message = format!("{error:#}"); // with the causes of the error
+            let quit_key = self.configuration.controls.quit.get_key();
+            last_frame = terminal
+                .draw(|frame| {
+                    StartupErrorWidget::new(&message, RETRY_KEY, quit_key).render_ref(frame.area(), frame.buffer_mut())
+                })?
+                .buffer
+                .clone();
+            loop {
+                let Event::Key(key_event) = event::read()? else {
+                    continue;
+                };
+                let Ok(key) = io::key_event_to_model_mapping::key_event_to_key(&key_event) else {
+                    continue;
+                };
+                if key == RETRY_KEY {
+                    break;
+                }
+                self.app_state.update(key);
+                if !self.app_state.is_running() {
+                    return Err(error);
+                }
             }
-            _ = terminal.draw(|frame| self.draw(frame, &main_widget, &mut main_state));
         }
-        Ok(())
     }
 
     fn draw(&mut self, frame: &mut Frame, main_widget: &MainWidget, state: &mut MainState) {
@@ -68,15 +201,22 @@ impl<T: Transit, F: ConstructWorkflow<T>> App<T, F> {
         &mut self,
         view_model: &MainViewModel,
         state: &MainState,
+        workflow: &Workflow<T>,
     ) -> eyre::Result<Option<Key>> {
         if event::poll(Duration::from_millis(250))? {
             //TODO: Move to io maybe?
             {
                 if let Event::Key(key_event) = event::read()? {
-                    let key = io::key_event_to_model_mapping::key_event_to_key(&key_event)?;
+                    // keys tuiflow has no name for can't be controls
+                    let Ok(key) = io::key_event_to_model_mapping::key_event_to_key(&key_event) else {
+                        return Ok(None);
+                    };
+                    if view_model.route_key(state, &key) != KeyRouting::Control {
+                        return Ok(Some(key));
+                    }
                     self.app_state.update(key);
 
-                    if view_model.needs_update(state, &self.workflow, &key) {
+                    if view_model.needs_update(state, workflow, &key) {
                         return Ok(Some(key));
                     }
                 }
@@ -84,8 +224,4 @@ impl<T: Transit, F: ConstructWorkflow<T>> App<T, F> {
         }
         Ok(None)
     }
-
-    fn update(&mut self, view_model: &mut MainViewModel, state: &mut MainState, key: &Key) {
-        view_model.update(state, &mut self.workflow, &key);
-    }
 }
@@ -0,0 +1,153 @@
+use crate::configuration::AppConfiguration;
+use serde_yaml::value::{Tag, TaggedValue};
+use serde_yaml::{Mapping, Value};
+
+/// The version of the flow file format this build reads. Files without a `version` key are version 1.
+pub const CURRENT_FLOW_VERSION: u64 = 2;
+
+type Migration = fn(&mut Mapping);
+
+// compatibility table: the migration at index i upgrades version i + 1 to version i + 2
+const MIGRATIONS: [Migration; (CURRENT_FLOW_VERSION - 1) as usize] = [migrate_guard_into_when];
+
+/// Parses a flow file of any supported version into the current structures.
+pub fn parse_flow_file(yaml: &str) -> eyre::Result<AppConfiguration> {
+    let document = migrate(serde_yaml::from_str(yaml)?)?;
+    Ok(serde_yaml::from_value(document)?)
+}
+
+/// Upgrades a flow file of any supported version to the current version, e.g. for `tuiflow migrate`.
+/// Comments and formatting of the original file are not kept.
+pub fn migrate_flow_file(yaml: &str) -> eyre::Result<String> {
+    let document = migrate(serde_yaml::from_str(yaml)?)?;
+    Ok(serde_yaml::to_string(&document)?)
+}
+
+/// Version 2 checks the line with a `!LineMatches` condition in `when` instead of a `guard` regex.
+fn migrate_guard_into_when(flow: &mut Mapping) {
+    let Some(Value::Mapping(states)) = flow.get_mut("states") else {
+        return;
+    };
+    let transitions = states
+        .values_mut()
+        .filter_map(|state| state.get_mut("transitions"))
+        .filter_map(Value::as_sequence_mut)
+        .flatten()
+        .filter_map(Value::as_mapping_mut);
+    for transition in transitions {
+        let Some(guard) = transition.remove("guard") else {
+            continue;
+        };
+        let line_matches = tagged("LineMatches", guard);
+        let when = match transition.remove("when") {
+            Some(when) => tagged("And", Value::Sequence(vec![line_matches, when])),
+            None => line_matches,
+        };
+        transition.insert("when".into(), when);
+    }
+}
+
+fn tagged(tag: &str, value: Value) -> Value {
+    Value::Tagged(Box::new(TaggedValue { tag: Tag::new(tag), value }))
+}
+
+fn migrate(mut document: Value) -> eyre::Result<Value> {
+    let flow = document
+        .as_mapping_mut()
+        .ok_or_else(|| eyre::eyre!("A flow file must be a YAML mapping"))?;
+    let version = match flow.get("version") {
+        None => 1,
+        Some(Value::Number(version)) => version
+            .as_u64()
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| eyre::eyre!("Unknown flow file version {version:?}"))?,
+        Some(version) => return Err(eyre::eyre!("Unknown flow file version {version:?}")),
+    };
+    if version > CURRENT_FLOW_VERSION {
+        return Err(eyre::eyre!(
+            "Flow file version {version} is newer than the supported version {CURRENT_FLOW_VERSION}. Please update tuiflow."
+        ));
+    }
+
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        migration(flow);
+    }
+    let mut migrated_flow = Mapping::new();
+    migrated_flow.insert("version".into(), CURRENT_FLOW_VERSION.into());
+    flow.iter()
+        .filter(|(key, _)| key.as_str() != Some("version"))
+        .for_each(|(key, value)| {
+            migrated_flow.insert(key.clone(), value.clone());
+        });
+    Ok(Value::Mapping(migrated_flow))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{migrate_flow_file, parse_flow_file, CURRENT_FLOW_VERSION};
+    use crate::configuration::ConditionConfiguration;
+
+    const V1_FLOW: &str = r#"
+app_title: test
+controls:
+  selection_up: { name: up, key: !Char 'k' }
+  selection_down: { name: down, key: !Char 'j' }
+  quit: { name: quit, key: !Char 'q' }
+  custom_controls: {}
+initial_command: ls
+initial_state: files
+initial_cli_output_variable_set_extractor: (?<file>.+)
+states:
+  files:
+    line_display_pattern: <file>
+"#;
+
+    #[test]
+    fn migrate_flow_file_without_version_adds_current_version() {
+        let migrated = migrate_flow_file(V1_FLOW).unwrap();
+
+        assert!(migrated.contains(&format!("version: {CURRENT_FLOW_VERSION}")));
+        assert_eq!(parse_flow_file(&migrated).unwrap(), parse_flow_file(V1_FLOW).unwrap());
+    }
+
+    #[test]
+    fn parse_flow_file_of_version_1_rewrites_guard_into_when() {
+        let v1_flow = format!(
+            "{V1_FLOW}    transitions:
+      - {{ control_name: restart, cli_output_variable_set_extractor: (?<pod>.+), command_pattern: restart, guard: Crash$ }}
+      - control_name: delete
+        cli_output_variable_set_extractor: (?<pod>.+)
+        command_pattern: delete
+        guard: Error$
+        when: !Exists pod
+"
+        );
+
+        let flow = parse_flow_file(&v1_flow).unwrap();
+        let transitions = &flow.states["files"].transitions;
+        let line_matches = |regex: &str| ConditionConfiguration::LineMatches(regex.to_string());
+        assert_eq!(transitions[0].guard, None);
+        assert_eq!(transitions[0].when, Some(line_matches("Crash$")));
+        assert_eq!(
+            transitions[1].when,
+            Some(ConditionConfiguration::And(vec![line_matches("Error$"), ConditionConfiguration::Exists("pod".to_string())]))
+        );
+        assert_eq!(parse_flow_file(&migrate_flow_file(&v1_flow).unwrap()).unwrap(), flow);
+    }
+
+    #[test]
+    fn parse_flow_file_with_too_new_version_fails_with_clear_error() {
+        let too_new = format!("version: {}\n{V1_FLOW}", CURRENT_FLOW_VERSION + 1);
+
+        let error = parse_flow_file(&too_new).unwrap_err().to_string();
+        assert!(error.contains("is newer than the supported version"));
+    }
+
+    #[test]
+    fn parse_flow_file_with_unknown_version_fails_with_clear_error() {
+        let unknown = format!("version: latest\n{V1_FLOW}");
+
+        let error = parse_flow_file(&unknown).unwrap_err().to_string();
+        assert!(error.contains("Unknown flow file version"));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::configuration::RequirementConfiguration;
+use regex::Regex;
+use std::cmp::Ordering;
+use tuiflow_model_contracts::command_runner::{CommandRunner, ExitStatus};
+
+const COMMAND_NOT_FOUND_EXIT_CODE: i32 = 127; // how sh exits if it can't find the command
+
+/// Runs the version command of every required tool and fails with a summary of all missing or outdated ones.
+pub(crate) fn check_requirements<R: CommandRunner>(
+    command_runner: &R,
+    requirements: &[RequirementConfiguration],
+) -> eyre::Result<()> {
+    let problems = requirements
+        .iter()
+        .map(|requirement| check_requirement(command_runner, requirement))
+        .collect::<eyre::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(eyre::eyre!(
+        "This flow requires tools that are missing or outdated:\n{}",
+        problems
+            .iter()
+            .map(|problem| format!("  - {problem}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    ))
+}
+
+fn check_requirement<R: CommandRunner>(
+    command_runner: &R,
+    requirement: &RequirementConfiguration,
+) -> eyre::Result<Option<String>> {
+    let tool = &requirement.tool;
+    let version_command = &requirement.version_command;
+    let version_pattern = Regex::new(&requirement.version_pattern)?;
+    let output = match command_runner.run_command(&version_command.clone().into()) {
+        // the error sh prints for a missing tool contains a number, which must not be read as its version
+        Ok(output) if output.exit_status == ExitStatus::Code(COMMAND_NOT_FOUND_EXIT_CODE) => {
+            return Ok(Some(format!("{tool}: not installed (`{version_command}` was not found)")));
+        }
+        Ok(output) if !output.is_success() => {
+            return Ok(Some(format!("{tool}: not installed (`{version_command}` failed with {})", output.exit_status)));
+        }
+        // some tools (e.g. `java -version`) print their version to stderr
+        Ok(output) if !output.stdout.trim().is_empty() => output.stdout,
+        Ok(output) if !output.stderr.trim().is_empty() => output.stderr,
+        _ => return Ok(Some(format!("{tool}: not installed (`{version_command}` printed nothing)"))),
+    };
+
+    let Some(installed_version) = version_pattern
+        .captures(&output)
+        .and_then(|captures| captures.name("version"))
+        .map(|version| version.as_str())
+    else {
+        return Ok(Some(format!(
+            "{tool}: could not read the version from the output of `{version_command}`"
+        )));
+    };
+
+    let is_too_old = |min_version: &&String| compare_versions(installed_version, min_version) == Ordering::Less;
+    if let Some(min_version) = requirement.min_version.as_ref().filter(is_too_old) {
+        return Ok(Some(format!("{tool}: version {installed_version} is installed, at least {min_version} is required")));
+    }
+    let is_too_new = |max_version: &&String| compare_versions(installed_version, max_version) == Ordering::Greater;
+    if let Some(max_version) = requirement.max_version.as_ref().filter(is_too_new) {
+        return Ok(Some(format!("{tool}: version {installed_version} is installed, at most {max_version} is supported")));
+    }
+    Ok(None)
+}
+
+/// Compares dotted versions numerically, missing parts count as 0 (so `2.1` equals `2.1.0`).
+fn compare_versions(left: &str, right: &str) -> Ordering {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (left, right) = (parse(left), parse(right));
+    (0..left.len().max(right.len()))
+        .map(|idx| {
+            let left_part = left.get(idx).copied().unwrap_or(0);
+            let right_part = right.get(idx).copied().unwrap_or(0);
+            left_part.cmp(&right_part)
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_requirements, compare_versions};
+    use crate::configuration::RequirementConfiguration;
+    use std::cmp::Ordering;
+    use tuiflow_model_contracts::command_runner::{CommandOutput, ExitStatus, MockCommandRunner};
+
+    fn git_requirement(min_version: Option<&str>) -> RequirementConfiguration {
+        RequirementConfiguration {
+            tool: "git".to_string(),
+            version_command: "git --version".to_string(),
+            version_pattern: r"(?<version>\d+(\.\d+)*)".to_string(),
+            min_version: min_version.map(str::to_string),
+            max_version: None,
+        }
+    }
+
+    #[test]
+    fn compare_versions_compares_parts_numerically() {
+        assert_eq!(compare_versions("2.10.0", "2.9"), Ordering::Greater);
+        assert_eq!(compare_versions("2.1", "2.1.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.99", "2"), Ordering::Less);
+    }
+
+    #[test]
+    fn check_requirements_with_sufficient_version_passes() {
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_command()
+            .returning(|_| Ok(CommandOutput::from_stdout("git version 2.39.1\n".to_string())));
+
+        assert!(check_requirements(&command_runner, &[git_requirement(Some("2.30"))]).is_ok());
+    }
+
+    #[test]
+    fn check_requirements_reports_outdated_and_missing_tools() {
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_command()
+            .returning(|cmd| match cmd.command.as_str() {
+                "git --version" => Ok(CommandOutput::from_stdout("git version 2.20.0".to_string())),
+                _ => Ok(CommandOutput::from_stdout("".to_string())),
+            });
+        let mut kubectl_requirement = git_requirement(None);
+        kubectl_requirement.tool = "kubectl".to_string();
+        kubectl_requirement.version_command = "kubectl version --client".to_string();
+
+        let message = check_requirements(&command_runner, &[git_requirement(Some("2.30")), kubectl_requirement])
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("git: version 2.20.0 is installed, at least 2.30 is required"));
+        assert!(message.contains("kubectl: not installed"));
+    }
+
+    #[test]
+    fn check_requirements_reports_tool_that_sh_did_not_find_as_not_installed() {
+        let mut command_runner = MockCommandRunner::default();
+        command_runner.expect_run_command().returning(|_| {
+            Ok(CommandOutput {
+                stderr: "sh: 1: git: not found\n".to_string(),
+                exit_status: ExitStatus::Code(127),
+                ..CommandOutput::from_stdout("".to_string())
+            })
+        });
+
+        let message = check_requirements(&command_runner, &[git_requirement(None)]).unwrap_err().to_string();
+        assert!(message.contains("git: not installed (`git --version` was not found)"));
+    }
+}
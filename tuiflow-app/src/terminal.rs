@@ -0,0 +1,38 @@
+use ratatui::crossterm::{cursor, execute, terminal};
+use ratatui::prelude::CrosstermBackend;
+use ratatui::Terminal;
+use std::fs::File;
+use std::panic;
+use std::sync::Once;
+
+/// Draws on the controlling terminal instead of stdout, so stdout stays free for the output of terminal states
+/// (e.g. `cd "$(tuiflow pick_dir.yaml)"`).
+pub type TtyTerminal = Terminal<CrosstermBackend<File>>;
+
+static PANIC_HOOK: Once = Once::new();
+
+pub fn init() -> eyre::Result<TtyTerminal> {
+    PANIC_HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore();
+            hook(info);
+        }));
+    });
+
+    let mut tty = open_tty()?;
+    terminal::enable_raw_mode()?;
+    execute!(tty, terminal::EnterAlternateScreen)?;
+    Ok(Terminal::new(CrosstermBackend::new(tty))?)
+}
+
+pub fn restore() {
+    _ = terminal::disable_raw_mode();
+    if let Ok(mut tty) = open_tty() {
+        _ = execute!(tty, terminal::LeaveAlternateScreen, cursor::Show);
+    }
+}
+
+fn open_tty() -> std::io::Result<File> {
+    File::options().read(true).write(true).open("/dev/tty")
+}
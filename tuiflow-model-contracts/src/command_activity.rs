@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// The commands a runner is waiting for and the request to cancel them, shared with the ui that waits for the runner.
+/// Clones share the same commands and request.
+#[derive(Clone, Default)]
+pub struct CommandActivity {
+    running_commands: Arc<Mutex<Vec<RunningCommand>>>,
+    next_running_command_id: Arc<AtomicU64>,
+    is_cancel_requested: Arc<AtomicBool>,
+}
+
+/// A command a runner is waiting for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunningCommand {
+    id: u64,
+    pub command: String,
+    pub started_at: Instant,
+}
+
+/// Keeps the command listed as running until it is dropped.
+pub struct RunningCommandGuard {
+    id: u64,
+    running_commands: Arc<Mutex<Vec<RunningCommand>>>,
+}
+
+impl Drop for RunningCommandGuard {
+    fn drop(&mut self) {
+        if let Ok(mut running_commands) = self.running_commands.lock() {
+            running_commands.retain(|running_command| running_command.id != self.id);
+        }
+    }
+}
+
+impl CommandActivity {
+    /// Lists the command as running, so the ui can show it while it waits.
+    pub fn start(&self, command: &str) -> RunningCommandGuard {
+        let id = self.next_running_command_id.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut running_commands) = self.running_commands.lock() {
+            running_commands.push(RunningCommand {
+                id,
+                command: command.to_string(),
+                started_at: Instant::now(),
+            });
+        }
+        RunningCommandGuard {
+            id,
+            running_commands: Arc::clone(&self.running_commands),
+        }
+    }
+
+    /// The running commands, the longest running first.
+    pub fn get_running_commands(&self) -> Vec<RunningCommand> {
+        self.running_commands
+            .lock()
+            .map(|running_commands| running_commands.clone())
+            .unwrap_or_default()
+    }
+
+    /// Asks the runners to kill their running commands and fail, until the request is cleared.
+    pub fn request_cancel(&self) {
+        self.is_cancel_requested.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancel_requested(&self) -> bool {
+        self.is_cancel_requested.load(Ordering::Relaxed)
+    }
+
+    pub fn clear_cancel_request(&self) {
+        self.is_cancel_requested.store(false, Ordering::Relaxed);
+    }
+}
@@ -1,20 +1,44 @@
+use crate::output_stream::OutputStream;
 use mockall::mock;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use std::ops::Deref;
+use std::time::Duration;
 
-pub trait CommandRunner: Clone
+pub trait CommandRunner: Clone + Sync
 where
 {
-    type Command: Deref<Target = str> + From<String>;
-    fn run_command(&self, command: &Self::Command) -> Result<String, CommandRunnerError>;
+    type Command: Deref<Target = str> + From<String> + Sync;
+    fn run_command(&self, command: &Self::Command) -> Result<CommandOutput, CommandRunnerError>;
+    /// Runs the commands at the same time, the outputs are in the order of the commands.
+    fn run_parallel_commands(&self, commands: &[Self::Command]) -> Vec<Result<CommandOutput, CommandRunnerError>> {
+        std::thread::scope(|scope| {
+            commands
+                .iter()
+                .map(|command| scope.spawn(move || self.run_command(command)))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|handle| handle.join().expect("A command thread panicked. Please report this issue on github."))
+                .collect()
+        })
+    }
+    /// Runs the command attached to the terminal so the user can interact with it.
+    fn run_interactive_command(&self, command: &Self::Command) -> Result<(), CommandRunnerError>;
+    /// Starts the command and returns right away, its output arrives line by line until the stream is dropped.
+    fn run_streaming_command(&self, command: &Self::Command) -> Result<OutputStream, CommandRunnerError>;
     fn new() -> Self;
+    /// Kills commands that run longer than the timeout instead of waiting for them.
+    fn with_timeout(self, timeout: Duration) -> Self;
+    /// The command as the runner runs it, as its errors name it.
+    fn get_full_command(command: &Self::Command) -> String {
+        command.to_string()
+    }
 }
 
 pub struct MockCommand {
     pub command: String,
 }
-impl<'a> From<String> for MockCommand {
+impl From<String> for MockCommand {
     fn from(value: String) -> Self {
         Self {
             command: value.to_string(),
@@ -39,19 +63,114 @@ mock! {
 
     impl CommandRunner for CommandRunner {
         type Command=MockCommand;
-        fn run_command<'a>(&self, command: &<MockCommandRunner as CommandRunner>::Command) -> Result<String, CommandRunnerError>;
+        fn run_command<'a>(&self, command: &<MockCommandRunner as CommandRunner>::Command) -> Result<CommandOutput, CommandRunnerError>;
+        fn run_interactive_command(&self, command: &<MockCommandRunner as CommandRunner>::Command) -> Result<(), CommandRunnerError>;
+        fn run_streaming_command(&self, command: &<MockCommandRunner as CommandRunner>::Command) -> Result<OutputStream, CommandRunnerError>;
         fn new() -> Self;
+        fn with_timeout(self, timeout: Duration) -> Self;
     }
 }
 
+/// What a command that ran to its end wrote and how it exited.
 #[derive(Debug, Clone, PartialEq)]
-pub struct CommandRunnerError {
-    pub command: String,
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_status: ExitStatus,
+    pub duration: Duration, // the wall time from starting the command until it exited
+}
+
+impl CommandOutput {
+    /// The output of a command that succeeded right away without writing to stderr.
+    pub fn from_stdout(stdout: String) -> Self {
+        Self {
+            stdout,
+            stderr: String::new(),
+            exit_status: ExitStatus::Code(0),
+            duration: Duration::ZERO,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.exit_status == ExitStatus::Code(0)
+    }
+
+    /// The lines of stdout followed by the lines of stderr.
+    pub fn get_stdout_and_stderr(&self) -> String {
+        [&self.stdout, &self.stderr]
+            .iter()
+            .map(|output| output.trim_end_matches('\n'))
+            .filter(|output| !output.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitStatus {
+    Code(i32),   // the command exited with the code
+    Signal(i32), // the command was killed by the signal
+}
+
+impl Display for ExitStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ExitStatus::Code(code) => write!(f, "exit code {code}"),
+            ExitStatus::Signal(signal) => write!(f, "signal {signal}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandRunnerError {
+    Spawn { command: String, reason: String },                 // the command could not be started
+    Cancelled { command: String },                             // the user cancelled the command while it was running
+    Timeout { command: String, timeout: Duration },            // the command was killed after running for too long
+    NonUtf8Output { command: String, output: CommandOutput },  // stdout is not UTF-8, the output holds it lossily
+    FailedExit { command: String, output: CommandOutput },     // a non-zero exit the transition treats as failure
+}
+
+impl CommandRunnerError {
+    pub fn get_command(&self) -> &str {
+        match self {
+            CommandRunnerError::Spawn { command, .. }
+            | CommandRunnerError::Cancelled { command }
+            | CommandRunnerError::Timeout { command, .. }
+            | CommandRunnerError::NonUtf8Output { command, .. }
+            | CommandRunnerError::FailedExit { command, .. } => command,
+        }
+    }
+
+    /// What the command wrote and how it exited, if it ran to its end.
+    pub fn get_output(&self) -> Option<&CommandOutput> {
+        match self {
+            CommandRunnerError::NonUtf8Output { output, .. } | CommandRunnerError::FailedExit { output, .. } => {
+                Some(output)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, CommandRunnerError::Cancelled { .. })
+    }
 }
 
 impl Display for CommandRunnerError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(f, "Failed to run command '{}'", self.command)
+        match self {
+            CommandRunnerError::Spawn { command, reason } => write!(f, "Failed to start command '{command}': {reason}"),
+            CommandRunnerError::Cancelled { command } => write!(f, "Cancelled command '{command}'"),
+            CommandRunnerError::Timeout { command, timeout } => {
+                write!(f, "Command '{command}' timed out after {:.1}s", timeout.as_secs_f32())
+            }
+            CommandRunnerError::NonUtf8Output { command, .. } => {
+                write!(f, "Command '{command}' wrote output that is not UTF-8")
+            }
+            CommandRunnerError::FailedExit { command, output } => {
+                write!(f, "Command '{command}' failed with {}", output.exit_status)
+            }
+        }
     }
 }
 
@@ -8,7 +8,7 @@ pub struct Control {
     key: Key,
 }
 
-impl<'a> Control {
+impl Control {
     pub fn new(name: &str, key: Key) -> Self {
         Self {
             name: String::from(name),
@@ -1,13 +1,16 @@
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Display { // Make this iterable
     pub lines: Vec<Line>,
+    pub item_keys: Vec<String>, // what identifies the item of each line across refreshes, empty if the lines are the items
 }
 
-impl Default for Display {
-    fn default() -> Self {
-        Self {
-            lines: Default::default(),
-        }
+impl Display {
+    /// What identifies the item the line shows, the line itself if the display has no item keys.
+    pub fn get_item_key(&self, line_index: usize) -> Option<&str> {
+        self.item_keys
+            .get(line_index)
+            .or_else(|| self.lines.get(line_index).map(|line| &line.0))
+            .map(String::as_str)
     }
 }
 
@@ -6,6 +6,13 @@ use crate::control::Key;
 pub enum StateTransitionError {
     VariableMappingError(VariableMappingError),
     ControlNotFound(Key),
+    NoApplicableTransition(Key),
+    InputMismatch(String),
+    EmptyOutput(String),
+    HistoryEmpty,
+    NotRefreshable,
+    HeldStateLeft, // the state a held control was resolved in is no longer shown
+    RefreshRouted(String), // rerunning the command of the state led to the named state instead
     CommandExecutionError(CommandRunnerError),
 }
 
@@ -18,6 +25,27 @@ impl Display for StateTransitionError {
             StateTransitionError::ControlNotFound(control) => {
                 write!(f, "Control not found: {control}")
             }
+            StateTransitionError::NoApplicableTransition(control) => {
+                write!(f, "No transition of control {control} applies to the selected line")
+            }
+            StateTransitionError::InputMismatch(input) => {
+                write!(f, "Input '{input}' does not match the expected format")
+            }
+            StateTransitionError::EmptyOutput(message) => {
+                write!(f, "{message}")
+            }
+            StateTransitionError::HistoryEmpty => {
+                write!(f, "There is no previous state to go back to")
+            }
+            StateTransitionError::NotRefreshable => {
+                write!(f, "The current state was not built by a command that could run again without confirmation")
+            }
+            StateTransitionError::HeldStateLeft => {
+                write!(f, "The state the control was confirmed in is no longer shown, nothing ran")
+            }
+            StateTransitionError::RefreshRouted(state_name) => {
+                write!(f, "Running the command of the state again led to state {state_name}, the previous lines are kept")
+            }
             StateTransitionError::CommandExecutionError(command) => {
                 write!(f, "CLI command execution failed: {command}")
             }
@@ -25,6 +53,8 @@ impl Display for StateTransitionError {
     }
 }
 
+impl Error for StateTransitionError {}
+
 #[derive(Debug, PartialEq)]
 pub struct VariableExtractorCompilationError(pub String);
 impl Display for VariableExtractorCompilationError {
@@ -50,6 +80,12 @@ impl Error for VariableMappingError {}
 #[derive(Debug)]
 pub struct InitialTransitionError(StateTransitionError);
 
+impl InitialTransitionError {
+    pub fn into_transition_error(self) -> StateTransitionError {
+        self.0
+    }
+}
+
 impl From<StateTransitionError> for InitialTransitionError {
     fn from(value: StateTransitionError) -> Self {
         InitialTransitionError(value)
@@ -1,5 +1,8 @@
+pub mod command_activity;
 pub mod command_runner;
 pub mod terminal_flow;
 pub mod display;
 pub mod control;
-pub mod error;
\ No newline at end of file
+pub mod error;
+pub mod output_stream;
+pub mod state_kind;
\ No newline at end of file
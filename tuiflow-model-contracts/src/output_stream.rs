@@ -0,0 +1,53 @@
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+/// The output of a command that keeps running, its lines arrive from another thread as they are printed.
+/// Dropping the stream stops the command.
+pub struct OutputStream {
+    lines: Receiver<String>,
+    stop: Option<Box<dyn FnOnce()>>,
+    is_finished: bool, // the command closed its output
+}
+
+impl OutputStream {
+    pub fn new(lines: Receiver<String>, stop: impl FnOnce() + 'static) -> Self {
+        Self {
+            lines,
+            stop: Some(Box::new(stop)),
+            is_finished: false,
+        }
+    }
+
+    /// The lines printed since the last call, without waiting for more.
+    pub fn take_new_lines(&mut self) -> Vec<String> {
+        let mut new_lines = vec![];
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) => new_lines.push(line),
+                Err(TryRecvError::Empty) => return new_lines,
+                Err(TryRecvError::Disconnected) => {
+                    self.is_finished = true;
+                    return new_lines;
+                }
+            }
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+}
+
+impl Drop for OutputStream {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop();
+        }
+    }
+}
+
+/// The change of the lines of a streaming state since it was last polled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamUpdate {
+    pub appended: usize, // lines added at the end
+    pub dropped: usize,  // lines removed from the start to stay within the cap
+}
@@ -0,0 +1,7 @@
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum StateKind {
+    #[default]
+    Lines,
+    Input { prompt: String },
+    Prose { markdown: bool },
+}
@@ -1,11 +1,73 @@
 use crate::control::{Control, Key};
 use crate::display::Display;
 use crate::error::StateTransitionError;
+use crate::output_stream::StreamUpdate;
+use crate::state_kind::StateKind;
 
 pub trait TerminalFlow {
-    fn run_control(&mut self, display_selection_index: Option<usize>, key: &Key) -> Result<(), StateTransitionError>;
+    fn run_control(&mut self, display_selection_index: Option<usize>, key: &Key) -> Result<ControlOutcome, StateTransitionError>;
+    /// Runs the control once for all marked lines instead of the selected one.
+    fn run_control_on_marked(&mut self, marked_display_indices: &[usize], key: &Key) -> Result<ControlOutcome, StateTransitionError>;
+    fn run_control_with_input(&mut self, display_selection_index: Option<usize>, input: &str, key: &Key) -> Result<ControlOutcome, StateTransitionError>;
+    /// Returns to the previous state as it was left, returns the selection to restore.
+    fn go_back(&mut self) -> Result<Option<usize>, StateTransitionError>;
+    /// Builds the current state again by rerunning the command it was built with.
+    fn refresh(&mut self) -> Result<(), StateTransitionError>;
+    /// Whether the current state was built by a command that `refresh` can run again, which a command that asks for confirmation can't.
+    fn is_refreshable(&self) -> bool;
+    /// The names of the previous states and the current state, the current one last.
+    fn get_breadcrumb(&self) -> Vec<String>;
     fn get_display(&self) -> &Display;
     fn get_state_title(&self) -> String;
     fn get_app_title(&self) -> &str;
     fn get_state_controls(&self) -> Vec<Control>;
+    /// The controls of the state that can run for the selected line, or in input states for the typed text.
+    fn get_applicable_state_controls(&self, display_selection_index: Option<usize>, input: Option<&str>) -> Vec<Control>;
+    fn get_state_kind(&self) -> StateKind;
+    fn get_terminal_handover(&self, display_selection_index: Option<usize>, input: Option<&str>, key: &Key) -> Option<TerminalHandover>;
+    fn get_marked_terminal_handover(&self, marked_display_indices: &[usize], key: &Key) -> Option<TerminalHandover>;
+    /// The question the user confirms before the control runs, `None` if it runs right away.
+    fn get_confirmation(&self, display_selection_index: Option<usize>, input: Option<&str>, key: &Key) -> Option<String>;
+    fn get_marked_confirmation(&self, marked_display_indices: &[usize], key: &Key) -> Option<String>;
+    /// Resolves the lines the control of the key runs for now, the marked ones or else the selected one,
+    /// and keeps them for `run_held_control`, e.g. while its confirmation is open. Replaces the held control.
+    fn hold_control(&mut self, display_selection_index: Option<usize>, marked_display_indices: &[usize], input: Option<&str>, key: &Key);
+    /// Runs the held control for exactly the lines it was resolved for, even if the lines changed meanwhile.
+    /// Fails without running it if its state was left.
+    fn run_held_control(&mut self) -> Result<ControlOutcome, StateTransitionError>;
+    fn get_held_terminal_handover(&self) -> Option<TerminalHandover>;
+    fn get_exit(&self) -> Option<&FlowExit>;
+    /// Runs the auto transition of the current state once its delay has passed, returns whether the state changed.
+    fn run_due_auto_transition(&mut self) -> bool;
+    /// Builds the current state again once its refresh interval has passed, returns whether it was rebuilt.
+    fn run_due_refresh(&mut self) -> bool;
+    /// Whether `run_due_auto_transition` or `run_due_refresh` would run a command now.
+    fn is_background_work_due(&self) -> bool;
+    /// The errors of transitions that ran without a key press since the last call, e.g. auto transitions or refreshes.
+    fn take_background_errors(&mut self) -> Vec<StateTransitionError>;
+    /// Adds the lines a streaming state received since the last poll, `None` if there are none.
+    fn poll_stream(&mut self) -> Option<StreamUpdate>;
+    /// Whether the current state keeps showing the newest lines of its stream while they arrive.
+    fn is_following_stream(&self) -> bool;
+}
+
+/// What running a control did to the flow.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlOutcome {
+    Transitioned,                        // the flow is in the next state or exits
+    Refreshed { toast: Option<String> }, // the control ran for its side effect and the current state was built again
+}
+
+/// What the flow prints and exits with once a terminal state is left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowExit {
+    pub output: String,
+    pub exit_code: u8,
+}
+
+/// How a control hands the terminal over to an interactive program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TerminalHandover {
+    Return, // the flow continues after the program exits
+    Exit,   // the flow ends after the program exits
 }
@@ -2,7 +2,7 @@ pub mod state;
 pub mod transition;
 pub mod variable_mapping;
 pub mod workflow;
-mod variable;
+pub mod variable;
 
 pub use tuiflow_model_contracts::control::Control;
 pub use tuiflow_model_contracts::display::Display;
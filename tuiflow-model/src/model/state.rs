@@ -1,4 +1,5 @@
 mod workflow_state;
+#[allow(clippy::module_inception)]
 mod state;
 
 use mockall::automock;
@@ -6,6 +7,7 @@ pub use workflow_state::*;
 pub use state::*;
 use tuiflow_model_contracts::control::Control;
 use tuiflow_model_contracts::error::StateTransitionError;
+use tuiflow_model_contracts::terminal_flow::TerminalHandover;
 use crate::model::variable::VariableSet;
 
 #[automock]
@@ -15,5 +17,27 @@ pub trait Transit: Sized {
         variables: &VariableSet,
     ) -> Result<State<Self>, StateTransitionError>;
 
+    /// Builds the next state again without handing the terminal to an interactive program.
+    fn rerun(&self, variables: &VariableSet) -> Result<State<Self>, StateTransitionError>;
+
+    /// Runs the transition once for several marked lines, as its batch mode decides.
+    fn run_batch(&self, variable_sets: &[VariableSet]) -> Result<State<Self>, StateTransitionError>;
+
+    fn rerun_batch(&self, variable_sets: &[VariableSet]) -> Result<State<Self>, StateTransitionError>;
+
+    /// The variables of the lines of output, as the next state of the transition shows them.
+    fn extract(&self, output: &str) -> Vec<VariableSet>;
+
     fn get_activation_control(&self) -> &Control;
+
+    fn is_applicable(&self, variables: &VariableSet) -> bool;
+
+    /// Whether the transition can run for the text typed into an input state, which only its condition
+    /// decides, as its extractor describes lines of output and not free text.
+    fn is_applicable_to_input(&self, variables: &VariableSet) -> bool;
+
+    /// The question to confirm before the transition runs for the lines, `None` if it runs right away.
+    fn get_confirmation(&self, variable_sets: &[VariableSet]) -> Option<String>;
+
+    fn get_terminal_handover(&self) -> Option<TerminalHandover>;
 }
\ No newline at end of file
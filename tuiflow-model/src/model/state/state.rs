@@ -1,17 +1,84 @@
 use crate::model::variable::VariableSet;
-use crate::state::workflow_state::WorkflowState;
+use crate::state::workflow_state::{AutoTrigger, WorkflowState};
 use crate::state::Transit;
 use crate::{Control, Display};
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::time::Instant;
 use tuiflow_model_contracts::control::Key;
+use tuiflow_model_contracts::display::Line;
 use tuiflow_model_contracts::error::StateTransitionError;
+use tuiflow_model_contracts::output_stream::{OutputStream, StreamUpdate};
+use tuiflow_model_contracts::state_kind::StateKind;
+use tuiflow_model_contracts::terminal_flow::{FlowExit, TerminalHandover};
 
-#[derive(Clone)]
 pub struct State<T: Transit> {
     workflow_state: Rc<RefCell<WorkflowState<T>>>,
     display: Display,
     arguments: Vec<VariableSet>,
+    entered_at: Instant,
+    refreshed_at: Instant, // when the lines were last built, for the refresh interval
+    origin: Option<StateOrigin<T>>, // how the state was built, None if it was not built by a transition
+    output_stream: Option<OutputStream>, // the running command of a streaming state
+    side_effect: Option<SideEffect>, // set if the transition stays in the current state instead of entering this one
+}
+
+/// What a transition that ran its command only for its side effect leaves behind.
+pub struct SideEffect {
+    pub toast: Option<String>, // the output of the command to show
+}
+
+struct StateOrigin<T: Transit> {
+    transition: Rc<T>,
+    variables: Vec<VariableSet>, // more than one if the transition ran for marked lines
+}
+
+impl<T: Transit> Clone for State<T> {
+    fn clone(&self) -> Self {
+        Self {
+            workflow_state: Rc::clone(&self.workflow_state),
+            display: self.display.clone(),
+            arguments: self.arguments.clone(),
+            entered_at: self.entered_at,
+            refreshed_at: self.refreshed_at,
+            origin: self.origin.as_ref().map(|origin| StateOrigin {
+                transition: Rc::clone(&origin.transition),
+                variables: origin.variables.clone(),
+            }),
+            output_stream: None, // the running command belongs to the original
+            side_effect: None,
+        }
+    }
+}
+
+/// A control with the lines it runs for resolved, to run it later for exactly these lines.
+pub struct HeldControl {
+    key: Key,
+    variable_sets: Vec<VariableSet>, // the selected line, or the marked lines if it runs for them
+    is_batch: bool,                  // whether it runs once for the marked lines
+    input: Option<String>,
+    display_selection_index: Option<usize>, // where the selection returns when going back
+    state_identity: StateIdentity,
+}
+
+impl HeldControl {
+    pub fn get_display_selection_index(&self) -> Option<usize> {
+        self.display_selection_index
+    }
+}
+
+/// Identifies the state a control was held in. A refreshed state stays the same, a state entered anew does not.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StateIdentity {
+    workflow_state: *const (),
+    entered_at: Instant,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AutoTransitionStatus {
+    Due(Key), // the transition of the key runs now
+    Waiting,  // the delay of the trigger has not passed yet
+    Never,    // no auto transition or its trigger does not apply
 }
 
 impl<T: Transit> State<T> {
@@ -28,24 +95,272 @@ impl<T: Transit> State<T> {
         display_selection_index: Option<usize>,
         key: &Key,
     ) -> Result<State<T>, StateTransitionError> {
-        let empty_set = VariableSet::empty();
-        let variable_set = display_selection_index
-            .map(|idx| {
-                self.arguments
-                    .get(idx)
-                    .expect("Display selection index out of bounds")
-            })
-            .unwrap_or(&empty_set);
+        let variable_set = self.get_selected_variable_set(display_selection_index);
 
         self.workflow_state
             .borrow()
             .transition(variable_set, key)
     }
 
+    /// Runs the transition of the key once for all marked lines.
+    pub fn transition_marked(
+        &self,
+        marked_display_indices: &[usize],
+        key: &Key,
+    ) -> Result<State<T>, StateTransitionError> {
+        let variable_sets = self.get_marked_variable_sets(marked_display_indices);
+
+        self.workflow_state
+            .borrow()
+            .transition_batch(&variable_sets, key)
+    }
+
+    pub fn transition_with_input(
+        &self,
+        display_selection_index: Option<usize>,
+        input: &str,
+        key: &Key,
+    ) -> Result<State<T>, StateTransitionError> {
+        let variable_set = self.get_selected_variable_set(display_selection_index);
+
+        self.workflow_state
+            .borrow()
+            .transition_with_input(variable_set, input, key)
+    }
+
+    pub fn exit(
+        &self,
+        display_selection_index: Option<usize>,
+        input: Option<&str>,
+        key: &Key,
+    ) -> Result<Option<FlowExit>, StateTransitionError> {
+        let variable_set = self.get_selected_variable_set(display_selection_index);
+
+        self.workflow_state
+            .borrow()
+            .exit(variable_set, input, key)
+    }
+
+    pub fn exit_marked(
+        &self,
+        marked_display_indices: &[usize],
+        key: &Key,
+    ) -> Result<Option<FlowExit>, StateTransitionError> {
+        let variable_sets = self.get_marked_variable_sets(marked_display_indices);
+
+        self.workflow_state
+            .borrow()
+            .exit_batch(&variable_sets, key)
+    }
+
+    pub fn get_terminal_handover(
+        &self,
+        display_selection_index: Option<usize>,
+        input: Option<&str>,
+        key: &Key,
+    ) -> Option<TerminalHandover> {
+        let variable_set = self.get_selected_variable_set(display_selection_index);
+
+        self.workflow_state
+            .borrow()
+            .get_terminal_handover(variable_set, input, key)
+    }
+
+    pub fn get_marked_terminal_handover(&self, marked_display_indices: &[usize], key: &Key) -> Option<TerminalHandover> {
+        let variable_sets = self.get_marked_variable_sets(marked_display_indices);
+
+        self.workflow_state
+            .borrow()
+            .get_batch_terminal_handover(&variable_sets, key)
+    }
+
+    pub fn get_confirmation(
+        &self,
+        display_selection_index: Option<usize>,
+        input: Option<&str>,
+        key: &Key,
+    ) -> Option<String> {
+        let variable_set = self.get_selected_variable_set(display_selection_index);
+
+        self.workflow_state
+            .borrow()
+            .get_confirmation(variable_set, input, key)
+    }
+
+    pub fn get_marked_confirmation(&self, marked_display_indices: &[usize], key: &Key) -> Option<String> {
+        let variable_sets = self.get_marked_variable_sets(marked_display_indices);
+
+        self.workflow_state
+            .borrow()
+            .get_batch_confirmation(&variable_sets, key)
+    }
+
+    /// Resolves the lines the control of the key runs for now, the marked ones or else the selected one.
+    pub fn hold_control(
+        &self,
+        display_selection_index: Option<usize>,
+        marked_display_indices: &[usize],
+        input: Option<&str>,
+        key: &Key,
+    ) -> HeldControl {
+        let is_batch = !marked_display_indices.is_empty();
+        let variable_sets = match is_batch {
+            true => self.get_marked_variable_sets(marked_display_indices),
+            false => self.get_selected_variable_set(display_selection_index).cloned().into_iter().collect(),
+        };
+        HeldControl {
+            key: *key,
+            variable_sets,
+            is_batch,
+            input: input.map(str::to_string),
+            display_selection_index: marked_display_indices.first().copied().or(display_selection_index),
+            state_identity: self.get_identity(),
+        }
+    }
+
+    pub fn is_holding_state(&self, held_control: &HeldControl) -> bool {
+        held_control.state_identity == self.get_identity()
+    }
+
+    pub fn exit_held(&self, held_control: &HeldControl) -> Result<Option<FlowExit>, StateTransitionError> {
+        let workflow_state = self.workflow_state.borrow();
+        match held_control.is_batch {
+            true => workflow_state.exit_batch(&held_control.variable_sets, &held_control.key),
+            false => workflow_state.exit(held_control.variable_sets.first(), held_control.input.as_deref(), &held_control.key),
+        }
+    }
+
+    pub fn transition_held(&self, held_control: &HeldControl) -> Result<State<T>, StateTransitionError> {
+        let workflow_state = self.workflow_state.borrow();
+        let variable_set = held_control.variable_sets.first();
+        match (held_control.is_batch, &held_control.input) {
+            (true, _) => workflow_state.transition_batch(&held_control.variable_sets, &held_control.key),
+            (false, Some(input)) => workflow_state.transition_with_input(variable_set, input, &held_control.key),
+            (false, None) => workflow_state.transition(variable_set, &held_control.key),
+        }
+    }
+
+    pub fn get_held_terminal_handover(&self, held_control: &HeldControl) -> Option<TerminalHandover> {
+        let workflow_state = self.workflow_state.borrow();
+        match held_control.is_batch {
+            true => workflow_state.get_batch_terminal_handover(&held_control.variable_sets, &held_control.key),
+            false => workflow_state.get_terminal_handover(
+                held_control.variable_sets.first(),
+                held_control.input.as_deref(),
+                &held_control.key,
+            ),
+        }
+    }
+
+    fn get_identity(&self) -> StateIdentity {
+        StateIdentity {
+            workflow_state: Rc::as_ptr(&self.workflow_state).cast(),
+            entered_at: self.entered_at,
+        }
+    }
+
+    fn get_selected_variable_set(&self, display_selection_index: Option<usize>) -> Option<&VariableSet> {
+        // prose can't be selected, its transitions always use the variables it was entered with
+        if self.workflow_state.borrow().is_prose() {
+            return self.arguments.first();
+        }
+
+        display_selection_index.map(|idx| {
+            self.arguments
+                .get(idx)
+                .expect("Display selection index out of bounds")
+        })
+    }
+
+    /// The variables of the marked lines, leaving out marks of lines the state no longer has.
+    fn get_marked_variable_sets(&self, marked_display_indices: &[usize]) -> Vec<VariableSet> {
+        marked_display_indices
+            .iter()
+            .filter_map(|idx| self.arguments.get(*idx))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_kind(&self) -> StateKind {
+        self.workflow_state.borrow().get_kind()
+    }
+
+    /// Builds the state again by rerunning the transition it originates from, `None` if it is not refreshable.
+    /// The rebuilt state counts as entered when this one was, so that a delayed auto transition still runs.
+    /// Fails if an exit code or output route of the transition leads to another state this time,
+    /// e.g. a state showing why a command failed stays until the user leaves it.
+    pub fn refresh(&self) -> Option<Result<State<T>, StateTransitionError>> {
+        let origin = self.origin.as_ref().filter(|_| self.is_refreshable())?;
+        let refreshed_state = match origin.variables.as_slice() {
+            [variables] => origin.transition.rerun(variables),
+            variable_sets => origin.transition.rerun_batch(variable_sets),
+        };
+        Some(refreshed_state.and_then(|mut state| {
+            if !Rc::ptr_eq(&state.workflow_state, &self.workflow_state) {
+                return Err(StateTransitionError::RefreshRouted(state.get_name()));
+            }
+            state.entered_at = self.entered_at;
+            Ok(state.with_origin(Rc::clone(&origin.transition), origin.variables.clone()))
+        }))
+    }
+
+    /// Whether the state can be built again, which the transition it originates from must allow without confirmation.
+    pub fn is_refreshable(&self) -> bool {
+        self.origin
+            .as_ref()
+            .is_some_and(|origin| origin.transition.get_confirmation(&origin.variables).is_none())
+    }
+
+    /// Whether the refresh interval of the state has passed since its lines were last built.
+    pub fn is_refresh_due(&self) -> bool {
+        self.is_refreshable()
+            && self.output_stream.is_none()
+            && self
+                .workflow_state
+                .borrow()
+                .get_refresh_interval()
+                .is_some_and(|refresh_interval| self.refreshed_at.elapsed() >= refresh_interval)
+    }
+
+    /// Waits for another interval before the next refresh, e.g. after a refresh failed.
+    pub fn postpone_refresh(&mut self) {
+        self.refreshed_at = Instant::now();
+    }
+
+    pub fn is_refreshed_on_back(&self) -> bool {
+        self.workflow_state.borrow().is_refreshed_on_back()
+    }
+
+    pub fn get_auto_transition_status(&self) -> AutoTransitionStatus {
+        let workflow_state = self.workflow_state.borrow();
+        let Some(auto_transition) = workflow_state.get_auto_transition() else {
+            return AutoTransitionStatus::Never;
+        };
+        match auto_transition.get_trigger() {
+            AutoTrigger::Always => AutoTransitionStatus::Due(auto_transition.get_key()),
+            AutoTrigger::SingleLine if self.display.lines.len() == 1 => {
+                AutoTransitionStatus::Due(auto_transition.get_key())
+            }
+            AutoTrigger::SingleLine => AutoTransitionStatus::Never,
+            AutoTrigger::Delay(delay) if self.entered_at.elapsed() >= delay => {
+                AutoTransitionStatus::Due(auto_transition.get_key())
+            }
+            AutoTrigger::Delay(_) => AutoTransitionStatus::Waiting,
+        }
+    }
+
     pub fn get_controls(&self) -> Vec<Control> {
         self.workflow_state.borrow().get_controls()
     }
 
+    pub fn get_applicable_controls(&self, display_selection_index: Option<usize>, input: Option<&str>) -> Vec<Control> {
+        let variable_set = self.get_selected_variable_set(display_selection_index);
+
+        self.workflow_state
+            .borrow()
+            .get_applicable_controls(variable_set, input)
+    }
+
     pub fn new(
         workflow_state: Rc<RefCell<WorkflowState<T>>>,
         arguments: Vec<VariableSet>,
@@ -55,6 +370,122 @@ impl<T: Transit> State<T> {
             workflow_state,
             display,
             arguments,
+            entered_at: Instant::now(),
+            refreshed_at: Instant::now(),
+            origin: None,
+            output_stream: None,
+            side_effect: None,
+        }
+    }
+
+    /// Creates the result of a transition that stays in the current state, to be built again from its origin.
+    pub fn new_side_effect(workflow_state: Rc<RefCell<WorkflowState<T>>>, toast: Option<String>) -> Self {
+        let mut state = Self::new(workflow_state, vec![]);
+        state.side_effect = Some(SideEffect { toast });
+        state
+    }
+
+    pub(crate) fn is_side_effect(&self) -> bool {
+        self.side_effect.is_some()
+    }
+
+    pub(crate) fn take_side_effect(&mut self) -> Option<SideEffect> {
+        self.side_effect.take()
+    }
+
+    /// Creates a state without lines that receives them from the running command of the stream.
+    pub fn new_streaming(
+        workflow_state: Rc<RefCell<WorkflowState<T>>>,
+        output_stream: OutputStream,
+        entry_variables: VariableSet,
+    ) -> Self {
+        let arguments = match workflow_state.borrow().is_prose() {
+            true => vec![entry_variables],
+            false => vec![],
+        };
+        Self {
+            workflow_state,
+            display: Display::default(),
+            arguments,
+            entered_at: Instant::now(),
+            refreshed_at: Instant::now(),
+            origin: None,
+            output_stream: Some(output_stream),
+            side_effect: None,
+        }
+    }
+
+    /// Adds the lines the stream received since the last poll, dropping the oldest beyond the cap.
+    pub fn poll_stream(&mut self) -> Option<StreamUpdate> {
+        let new_lines = self.output_stream.as_mut()?.take_new_lines();
+        if new_lines.is_empty() {
+            return None;
+        }
+
+        let workflow_state = self.workflow_state.borrow();
+        let stream_settings = workflow_state.get_stream()?;
+        let appended = if workflow_state.is_prose() {
+            self.display.lines.extend(new_lines.iter().map(|line| Line::from(line.as_str())));
+            new_lines.len()
+        } else {
+            // the lines are extracted as the transition that started the stream would have extracted its output
+            let origin = self.origin.as_ref()?;
+            let variable_sets = origin.transition.extract(&new_lines.join("\n"));
+            let display = workflow_state.get_display(&variable_sets);
+            self.display.lines.extend(display.lines);
+            self.display.item_keys.extend(display.item_keys);
+            let appended = variable_sets.len();
+            self.arguments.extend(variable_sets);
+            appended
+        };
+
+        let dropped = self.display.lines.len().saturating_sub(stream_settings.max_lines);
+        self.display.lines.drain(..dropped);
+        if !workflow_state.is_prose() {
+            self.display.item_keys.drain(..dropped);
+            self.arguments.drain(..dropped);
+        }
+        Some(StreamUpdate { appended, dropped })
+    }
+
+    pub fn is_following_stream(&self) -> bool {
+        self.output_stream.is_some()
+            && self
+                .workflow_state
+                .borrow()
+                .get_stream()
+                .is_some_and(|stream_settings| stream_settings.follow)
+    }
+
+    /// Stops the running command of a streaming state, keeping the lines it received.
+    pub fn stop_stream(&mut self) {
+        self.output_stream = None;
+    }
+
+    pub(crate) fn with_origin(mut self, transition: Rc<T>, variables: Vec<VariableSet>) -> Self {
+        self.origin = Some(StateOrigin { transition, variables });
+        self
+    }
+
+    /// Creates a state that displays the text as is and keeps the variables it was entered with.
+    pub fn new_prose(
+        workflow_state: Rc<RefCell<WorkflowState<T>>>,
+        text: &str,
+        entry_variables: VariableSet,
+    ) -> Self {
+        let display = Display {
+            lines: text.lines().map(Line::from).collect(),
+            item_keys: vec![],
+        };
+        Self {
+            workflow_state,
+            display,
+            arguments: vec![entry_variables],
+            entered_at: Instant::now(),
+            refreshed_at: Instant::now(),
+            origin: None,
+            output_stream: None,
+            side_effect: None,
         }
     }
 }
@@ -1,18 +1,101 @@
-use crate::model::variable::VariableSet;
+use crate::model::variable::{Variable, VariableName, VariableSet};
 use crate::state::state::State;
 use crate::state::Transit;
-use crate::variable_mapping::VariableInjector;
+use crate::variable_mapping::{RegexVariableExtractor, VariableExtractor, VariableInjector};
 use crate::{Control, Display};
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
 use tuiflow_model_contracts::control::Key;
 use tuiflow_model_contracts::error::StateTransitionError;
-use tuiflow_model_contracts::error::StateTransitionError::ControlNotFound;
+use tuiflow_model_contracts::error::StateTransitionError::{ControlNotFound, InputMismatch, NoApplicableTransition};
+use tuiflow_model_contracts::state_kind::StateKind;
+use tuiflow_model_contracts::terminal_flow::{FlowExit, TerminalHandover};
+
+#[derive(Clone)]
+pub enum WorkflowStateKind {
+    Lines,
+    Input {
+        prompt: String,
+        capture: InputCapture,
+    },
+    Prose {
+        markdown: bool,
+    },
+}
+
+/// How the text typed into an input state becomes variables.
+#[derive(Clone)]
+pub enum InputCapture {
+    Variable(VariableName),                        // the typed text as is, whatever it looks like
+    VariableSetExtractor(RegexVariableExtractor), // the variables the regex captures, the text has to match it
+}
+
+/// Ends the flow from a terminal state, printing the output filled with the selected variables.
+#[derive(Clone)]
+pub struct TerminalExit {
+    control: Control,
+    output: VariableInjector,
+    exit_code: u8,
+}
+
+impl TerminalExit {
+    pub fn new(control: Control, output: VariableInjector, exit_code: u8) -> Self {
+        Self {
+            control,
+            output,
+            exit_code,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutoTrigger {
+    Always,          // right after entering the state
+    SingleLine,      // right after entering the state if it shows exactly one line
+    Delay(Duration), // once the state has been shown for the duration
+}
+
+/// Runs the transition of the key without a key press, using the first line as selection.
+#[derive(Clone)]
+pub struct AutoTransition {
+    key: Key,
+    trigger: AutoTrigger,
+}
+
+impl AutoTransition {
+    pub fn new(key: Key, trigger: AutoTrigger) -> Self {
+        Self { key, trigger }
+    }
+
+    pub(crate) fn get_key(&self) -> Key {
+        self.key
+    }
+
+    pub(crate) fn get_trigger(&self) -> AutoTrigger {
+        self.trigger
+    }
+}
+
+/// Shows the output of the commands leading into the state while they run, instead of once they are done.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StreamSettings {
+    pub max_lines: usize, // older lines are dropped beyond
+    pub follow: bool,     // keep showing the newest line while the last one is selected
+}
 
 #[derive(Clone)]
 pub struct WorkflowState<T: Transit> {
     display_name: String,
     command_output_to_display: VariableInjector,
-    transitions: HashMap<Key, T>,
+    transitions: HashMap<Key, Vec<Rc<T>>>, // candidates per key in order of precedence, shared with the states they lead to
+    kind: WorkflowStateKind,
+    terminal_exit: Option<TerminalExit>,
+    auto_transition: Option<AutoTransition>,
+    is_refreshed_on_back: bool,
+    stream: Option<StreamSettings>,
+    refresh_interval: Option<Duration>,
+    item_key_variable: Option<String>, // identifies the item of a line across refreshes, else all its variables do
 }
 
 impl<T: Transit> WorkflowState<T> {
@@ -21,42 +104,315 @@ impl<T: Transit> WorkflowState<T> {
         display_variable_injector: VariableInjector,
         transitions: Vec<T>,
     ) -> Self {
-        let transition_mapping = transitions
-            .into_iter()
-            .map(|t: T| (t.get_activation_control().get_key(), t))
-            .collect::<HashMap<Key, T>>();
-
-        Self {
+        let mut state = Self {
             display_name: String::from(display_name),
             command_output_to_display: display_variable_injector,
-            transitions: transition_mapping,
-        }
+            transitions: HashMap::new(),
+            kind: WorkflowStateKind::Lines,
+            terminal_exit: None,
+            auto_transition: None,
+            is_refreshed_on_back: false,
+            stream: None,
+            refresh_interval: None,
+            item_key_variable: None,
+        };
+        transitions.into_iter().for_each(|t: T| {
+            let key = t.get_activation_control().get_key();
+            state.add_transition(key, t)
+        });
+        state
+    }
+
+    pub fn with_kind(mut self, kind: WorkflowStateKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_terminal_exit(mut self, terminal_exit: TerminalExit) -> Self {
+        self.terminal_exit = Some(terminal_exit);
+        self
+    }
+
+    pub fn with_auto_transition(mut self, auto_transition: AutoTransition) -> Self {
+        self.auto_transition = Some(auto_transition);
+        self
+    }
+
+    /// Builds the state again when navigating back to it instead of showing it as it was left.
+    pub fn with_refresh_on_back(mut self, is_refreshed_on_back: bool) -> Self {
+        self.is_refreshed_on_back = is_refreshed_on_back;
+        self
+    }
+
+    pub fn with_stream(mut self, stream: StreamSettings) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
+    /// Builds the state again every interval while it is shown, e.g. to watch pods or jobs.
+    pub fn with_refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = Some(refresh_interval);
+        self
+    }
+
+    pub fn with_item_key_variable(mut self, item_key_variable: String) -> Self {
+        self.item_key_variable = Some(item_key_variable);
+        self
+    }
+
+    pub(crate) fn get_refresh_interval(&self) -> Option<Duration> {
+        self.refresh_interval
+    }
+
+    pub(crate) fn get_stream(&self) -> Option<StreamSettings> {
+        self.stream
+    }
+
+    pub(crate) fn is_refreshed_on_back(&self) -> bool {
+        self.is_refreshed_on_back
+    }
+
+    pub(crate) fn get_auto_transition(&self) -> Option<&AutoTransition> {
+        self.auto_transition.as_ref()
     }
 
     pub fn add_transition(&mut self, key: Key, transition: T) {
-        self.transitions.insert(key, transition);
+        self.transitions.entry(key).or_default().push(Rc::new(transition));
     }
 
     pub(crate) fn transition(
         &self,
-        selected_variable_set: &VariableSet,
+        selected_variable_set: Option<&VariableSet>,
         key: &Key,
     ) -> Result<State<T>, StateTransitionError> {
-        if let Some(transition) = self.transitions.get(key) {
-            transition.run(selected_variable_set)
-        } else {
-            Err(ControlNotFound(*key))
+        let (transition, variable_set) = self.resolve_transition(selected_variable_set, None, key)?;
+        Self::run_transition(transition, &variable_set)
+    }
+
+    /// Runs the first transition of the key, as without a selected line there is nothing to match against.
+    pub(crate) fn transition_without_selection(
+        &self,
+        variables: &VariableSet,
+        key: &Key,
+    ) -> Result<State<T>, StateTransitionError> {
+        Self::run_transition(self.find_transition(None, key)?, variables)
+    }
+
+    /// Runs the first transition of the key that is applicable to every marked line, once for all of them.
+    pub(crate) fn transition_batch(
+        &self,
+        marked_variable_sets: &[VariableSet],
+        key: &Key,
+    ) -> Result<State<T>, StateTransitionError> {
+        let transition = self.find_batch_transition(marked_variable_sets, key)?;
+        let next_state = transition.run_batch(marked_variable_sets)?;
+        Ok(next_state.with_origin(Rc::clone(transition), marked_variable_sets.to_vec()))
+    }
+
+    /// Runs the transition and lets the next state remember it, so it can be built again later.
+    fn run_transition(transition: &Rc<T>, variables: &VariableSet) -> Result<State<T>, StateTransitionError> {
+        let next_state = transition.run(variables)?;
+        Ok(next_state.with_origin(Rc::clone(transition), vec![variables.clone()]))
+    }
+
+    pub(crate) fn transition_with_input(
+        &self,
+        selected_variable_set: Option<&VariableSet>,
+        input: &str,
+        key: &Key,
+    ) -> Result<State<T>, StateTransitionError> {
+        let (transition, variable_set) = self.resolve_transition(selected_variable_set, Some(input), key)?;
+        Self::run_transition(transition, &variable_set)
+    }
+
+    pub(crate) fn get_terminal_handover(
+        &self,
+        selected_variable_set: Option<&VariableSet>,
+        input: Option<&str>,
+        key: &Key,
+    ) -> Option<TerminalHandover> {
+        let (transition, _) = self.resolve_transition(selected_variable_set, input, key).ok()?;
+        transition.get_terminal_handover()
+    }
+
+    pub(crate) fn get_batch_terminal_handover(&self, marked_variable_sets: &[VariableSet], key: &Key) -> Option<TerminalHandover> {
+        self.find_batch_transition(marked_variable_sets, key)
+            .ok()?
+            .get_terminal_handover()
+    }
+
+    /// The question to confirm before the transition of the key runs, `None` if it runs right away.
+    pub(crate) fn get_confirmation(
+        &self,
+        selected_variable_set: Option<&VariableSet>,
+        input: Option<&str>,
+        key: &Key,
+    ) -> Option<String> {
+        let (transition, variable_set) = self.resolve_transition(selected_variable_set, input, key).ok()?;
+        transition.get_confirmation(std::slice::from_ref(&variable_set))
+    }
+
+    pub(crate) fn get_batch_confirmation(&self, marked_variable_sets: &[VariableSet], key: &Key) -> Option<String> {
+        self.find_batch_transition(marked_variable_sets, key)
+            .ok()?
+            .get_confirmation(marked_variable_sets)
+    }
+
+    /// The exit of the flow if the key is the control of a terminal state.
+    pub(crate) fn exit(
+        &self,
+        selected_variable_set: Option<&VariableSet>,
+        input: Option<&str>,
+        key: &Key,
+    ) -> Result<Option<FlowExit>, StateTransitionError> {
+        let Some(terminal_exit) = &self.terminal_exit else {
+            return Ok(None);
+        };
+        if terminal_exit.control.get_key() != *key {
+            return Ok(None);
         }
+
+        let input_variable_set = match input {
+            Some(input) => self.get_input_variable_set(selected_variable_set, input).transpose()?,
+            None => None,
+        };
+        let variable_set = input_variable_set
+            .as_ref()
+            .or(selected_variable_set)
+            .cloned()
+            .unwrap_or_else(VariableSet::empty);
+        Ok(Some(FlowExit {
+            output: terminal_exit.output.inject(&variable_set),
+            exit_code: terminal_exit.exit_code,
+        }))
     }
 
-    pub(crate) fn get_controls(&self) -> Vec<Control> {
+    /// The exit of the flow for several marked lines, their outputs one per line.
+    pub(crate) fn exit_batch(
+        &self,
+        marked_variable_sets: &[VariableSet],
+        key: &Key,
+    ) -> Result<Option<FlowExit>, StateTransitionError> {
+        let exits = marked_variable_sets
+            .iter()
+            .map(|variables| self.exit(Some(variables), None, key))
+            .collect::<Result<Option<Vec<FlowExit>>, _>>()?;
+        Ok(exits.and_then(|exits| {
+            let exit_code = exits.first()?.exit_code;
+            let output = exits.into_iter().map(|exit| exit.output).collect::<Vec<_>>().join("\n");
+            Some(FlowExit { output, exit_code })
+        }))
+    }
+
+    fn find_transition(
+        &self,
+        selected_variable_set: Option<&VariableSet>,
+        key: &Key,
+    ) -> Result<&Rc<T>, StateTransitionError> {
+        let candidates = self.transitions.get(key).ok_or(ControlNotFound(*key))?;
+        match selected_variable_set {
+            Some(variable_set) => candidates
+                .iter()
+                .find(|transition| transition.is_applicable(variable_set))
+                .ok_or(NoApplicableTransition(*key)),
+            None => candidates.first().ok_or(ControlNotFound(*key)),
+        }
+    }
+
+    /// The transition of the key for the typed input, or else for the selected line, and the variables it runs with.
+    /// The transitions of input states run for any typed text their condition allows.
+    fn resolve_transition(
+        &self,
+        selected_variable_set: Option<&VariableSet>,
+        input: Option<&str>,
+        key: &Key,
+    ) -> Result<(&Rc<T>, VariableSet), StateTransitionError> {
+        if let Some(input_variable_set) = input.and_then(|input| self.get_input_variable_set(selected_variable_set, input)) {
+            let input_variable_set = input_variable_set?;
+            let transition = self
+                .transitions
+                .get(key)
+                .ok_or(ControlNotFound(*key))?
+                .iter()
+                .find(|transition| transition.is_applicable_to_input(&input_variable_set))
+                .ok_or(NoApplicableTransition(*key))?;
+            return Ok((transition, input_variable_set));
+        }
+        // without a selected line (e.g. an empty list) only transitions applicable to an empty line may run
+        let variable_set = selected_variable_set.cloned().unwrap_or_else(VariableSet::empty);
+        Ok((self.find_transition(Some(&variable_set), key)?, variable_set))
+    }
+
+    /// The first transition of the key that is applicable to every marked line.
+    fn find_batch_transition(
+        &self,
+        marked_variable_sets: &[VariableSet],
+        key: &Key,
+    ) -> Result<&Rc<T>, StateTransitionError> {
         self.transitions
+            .get(key)
+            .ok_or(ControlNotFound(*key))?
             .iter()
-            .map(|(_, transition)| transition.get_activation_control().clone())
+            .find(|transition| marked_variable_sets.iter().all(|variables| transition.is_applicable(variables)))
+            .ok_or(NoApplicableTransition(*key))
+    }
+
+    /// Merges the variables captured from the input into the selected ones, `None` if this is no input state.
+    fn get_input_variable_set(
+        &self,
+        selected_variable_set: Option<&VariableSet>,
+        input: &str,
+    ) -> Option<Result<VariableSet, StateTransitionError>> {
+        let WorkflowStateKind::Input { capture, .. } = &self.kind else {
+            return None;
+        };
+
+        let input_variable_set = match capture {
+            InputCapture::Variable(variable_name) => {
+                Ok([Variable::new(variable_name.clone(), input.to_string())].into_iter().collect())
+            }
+            InputCapture::VariableSetExtractor(input_extractor) => input_extractor
+                .extract(input)
+                .into_iter()
+                .next()
+                .ok_or(InputMismatch(input.to_string())),
+        }
+        .map(|variable_set: VariableSet| variable_set.with_source_line(input.to_string()));
+        Some(input_variable_set.map(|input_variable_set| {
+            selected_variable_set
+                .map(|selected| selected.merged_with(&input_variable_set))
+                .unwrap_or(input_variable_set)
+        }))
+    }
+
+    pub(crate) fn get_controls(&self) -> Vec<Control> {
+        let mut controls: Vec<Control> = vec![];
+        self.transitions
+            .values()
+            .flatten()
+            .map(|transition| transition.get_activation_control())
+            .chain(self.terminal_exit.iter().map(|terminal_exit| &terminal_exit.control))
+            .for_each(|control| {
+                if !controls.contains(control) {
+                    controls.push(control.clone())
+                }
+            });
+        controls
+    }
+
+    /// The controls that can run for the selected line, or in input states for the typed text.
+    pub(crate) fn get_applicable_controls(&self, selected_variable_set: Option<&VariableSet>, input: Option<&str>) -> Vec<Control> {
+        self.get_controls()
+            .into_iter()
+            .filter(|control| {
+                let key = control.get_key();
+                matches!(self.exit(selected_variable_set, input, &key), Ok(Some(_)))
+                    || self.resolve_transition(selected_variable_set, input, &key).is_ok()
+            })
             .collect()
     }
 
-    pub(crate) fn get_display(&self, variable_set: &Vec<VariableSet>) -> Display {
+    pub(crate) fn get_display(&self, variable_set: &[VariableSet]) -> Display {
         let lines = variable_set
             .iter()
             .map(|set| {
@@ -66,22 +422,44 @@ impl<T: Transit> WorkflowState<T> {
             })
             .collect();
 
-        Display { lines }
+        let item_keys = variable_set
+            .iter()
+            .map(|set| set.get_item_key(self.item_key_variable.as_deref()))
+            .collect();
+        Display { lines, item_keys }
     }
 
     pub(crate) fn get_display_name(&self) -> String {
         self.display_name.clone()
     }
+
+    pub(crate) fn get_kind(&self) -> StateKind {
+        match &self.kind {
+            WorkflowStateKind::Lines => StateKind::Lines,
+            WorkflowStateKind::Input { prompt, .. } => StateKind::Input {
+                prompt: prompt.clone(),
+            },
+            WorkflowStateKind::Prose { markdown } => StateKind::Prose {
+                markdown: *markdown,
+            },
+        }
+    }
+
+    pub fn is_prose(&self) -> bool {
+        matches!(self.kind, WorkflowStateKind::Prose { .. })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::state::{MockTransit, State, WorkflowState};
-    use crate::variable_mapping::VariableInjector;
+    use crate::state::{InputCapture, MockTransit, State, TerminalExit, WorkflowState, WorkflowStateKind};
+    use tuiflow_model_contracts::terminal_flow::{FlowExit, TerminalHandover};
+    use crate::variable_mapping::{RegexVariableExtractor, VariableInjector};
     use std::cell::RefCell;
     use std::rc::Rc;
     use tuiflow_model_contracts::control::{Control, Key};
-    use crate::model::variable::VariableSet;
+    use crate::model::variable::{Variable, VariableSet};
+    use tuiflow_model_contracts::error::StateTransitionError;
 
     #[test]
     fn transition_with_existing_control_runs_transitions() {
@@ -100,12 +478,228 @@ mod tests {
         mock_transition
             .expect_get_activation_control()
             .return_const(activation_control.clone());
+        mock_transition
+            .expect_is_applicable()
+            .return_const(true);
 
         let variable_injector = VariableInjector::new("some pattern".to_string());
         let testee = WorkflowState::new("some state", variable_injector, vec![mock_transition]);
-        let target_state = testee.transition(&VariableSet::empty(), &activation_control.get_key());
+        let target_state = testee.transition(Some(&VariableSet::empty()), &activation_control.get_key());
         assert!(target_state.is_ok());
         assert_eq!(target_state.unwrap().get_name().as_str(), target_state_display_name);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn transition_with_multiple_transitions_on_key_runs_first_applicable() {
+        let activation_control = Control::new("some control", Key::Char('l'));
+        let transitions = [(false, "not applicable"), (true, "first applicable"), (true, "second applicable")]
+            .into_iter()
+            .map(|(applicable, target_state_display_name)| {
+                let mut mock_transition = MockTransit::new();
+                mock_transition
+                    .expect_get_activation_control()
+                    .return_const(activation_control.clone());
+                mock_transition
+                    .expect_is_applicable()
+                    .return_const(applicable);
+                mock_transition
+                    .expect_run()
+                    .returning(move |_| {
+                        let variable_injector = VariableInjector::new("some pattern".to_string());
+                        let target_state = WorkflowState::new(target_state_display_name, variable_injector, vec![]);
+                        Ok(State::new(Rc::new(RefCell::new(target_state)), vec![]))
+                    });
+                mock_transition
+            })
+            .collect::<Vec<_>>();
+
+        let variable_injector = VariableInjector::new("some pattern".to_string());
+        let testee = WorkflowState::new("some state", variable_injector, transitions);
+        let target_state = testee.transition(Some(&VariableSet::empty()), &activation_control.get_key());
+        assert_eq!(target_state.unwrap().get_name().as_str(), "first applicable");
+        assert_eq!(testee.get_controls(), vec![activation_control]);
+    }
+
+    #[test]
+    fn transition_without_applicable_transition_returns_error() {
+        let activation_control = Control::new("some control", Key::Char('l'));
+        let mut mock_transition = MockTransit::new();
+        mock_transition
+            .expect_get_activation_control()
+            .return_const(activation_control.clone());
+        mock_transition
+            .expect_is_applicable()
+            .return_const(false);
+        mock_transition
+            .expect_run()
+            .never();
+
+        let variable_injector = VariableInjector::new("some pattern".to_string());
+        let testee = WorkflowState::new("some state", variable_injector, vec![mock_transition]);
+        let target_state = testee.transition(Some(&VariableSet::empty()), &activation_control.get_key());
+        assert!(matches!(target_state, Err(StateTransitionError::NoApplicableTransition(Key::Char('l')))));
+    }
+
+    #[test]
+    fn transition_with_input_runs_transition_with_selected_and_input_variables() {
+        let activation_control = Control::new("some control", Key::Enter);
+        let mut mock_transition = MockTransit::new();
+        mock_transition
+            .expect_get_activation_control()
+            .return_const(activation_control.clone());
+        // the typed text is free text, it never has to match the extractor of the transition
+        mock_transition
+            .expect_is_applicable()
+            .never();
+        mock_transition
+            .expect_is_applicable_to_input()
+            .return_const(true);
+        mock_transition
+            .expect_run()
+            .once()
+            .withf(|variables| {
+                variables.contains(&Variable::new("path".into(), "/home".to_string()))
+                    && variables.contains(&Variable::new("message".into(), "fix: don't (really) panic".to_string()))
+                    && variables.get_source_line() == "fix: don't (really) panic"
+            })
+            .returning(|_| {
+                let target_state = WorkflowState::new("target state", VariableInjector::new("".to_string()), vec![]);
+                Ok(State::new(Rc::new(RefCell::new(target_state)), vec![]))
+            });
+
+        let input_kind = WorkflowStateKind::Input {
+            prompt: "commit message".to_string(),
+            capture: InputCapture::Variable("message".into()),
+        };
+        let testee = WorkflowState::new("some state", VariableInjector::new("".to_string()), vec![mock_transition])
+            .with_kind(input_kind);
+        let selected_variable_set = vec![Variable::new("path".into(), "/home".to_string())]
+            .into_iter()
+            .collect::<VariableSet>();
+        let target_state = testee.transition_with_input(Some(&selected_variable_set), "fix: don't (really) panic", &activation_control.get_key());
+        assert!(target_state.is_ok());
+    }
+
+    #[test]
+    fn transition_with_input_with_unmatching_input_returns_error() {
+        let activation_control = Control::new("some control", Key::Enter);
+        let mut mock_transition = MockTransit::new();
+        mock_transition
+            .expect_get_activation_control()
+            .return_const(activation_control.clone());
+        mock_transition
+            .expect_run()
+            .never();
+
+        let input_kind = WorkflowStateKind::Input {
+            prompt: "number".to_string(),
+            capture: InputCapture::VariableSetExtractor(RegexVariableExtractor::new("^(?<number>[0-9]+)$").unwrap()),
+        };
+        let testee = WorkflowState::new("some state", VariableInjector::new("".to_string()), vec![mock_transition])
+            .with_kind(input_kind);
+        let target_state = testee.transition_with_input(None, "abc", &activation_control.get_key());
+        assert!(matches!(target_state, Err(StateTransitionError::InputMismatch(_))));
+    }
+
+    #[test]
+    fn transition_without_selection_runs_first_transition_with_given_variables() {
+        let activation_control = Control::new("INIT", Key::Backspace);
+        let mut mock_transition = MockTransit::new();
+        mock_transition
+            .expect_get_activation_control()
+            .return_const(activation_control.clone());
+        mock_transition
+            .expect_is_applicable()
+            .never();
+        mock_transition
+            .expect_run()
+            .once()
+            .withf(|variables| variables.contains(&Variable::new("arg1".into(), "main".to_string())))
+            .returning(|_| {
+                let target_state = WorkflowState::new("target state", VariableInjector::new("".to_string()), vec![]);
+                Ok(State::new(Rc::new(RefCell::new(target_state)), vec![]))
+            });
+
+        let testee = WorkflowState::new("INIT", VariableInjector::new("".to_string()), vec![mock_transition]);
+        let initial_variables = vec![Variable::new("arg1".into(), "main".to_string())]
+            .into_iter()
+            .collect::<VariableSet>();
+        let target_state = testee.transition_without_selection(&initial_variables, &activation_control.get_key());
+        assert!(target_state.is_ok());
+    }
+
+    #[test]
+    fn exit_with_terminal_control_injects_selected_variables_into_output() {
+        let exit_control = Control::new("pick", Key::Enter);
+        let testee: WorkflowState<MockTransit> = WorkflowState::new("pick dir", VariableInjector::new("<dir>".to_string()), vec![])
+            .with_terminal_exit(TerminalExit::new(exit_control.clone(), VariableInjector::new("/home/<dir>".to_string()), 3));
+
+        let variable_set: VariableSet = vec![Variable::new("dir".into(), "docs".to_string())].into_iter().collect();
+        let exit = testee.exit(Some(&variable_set), None, &Key::Enter).unwrap().unwrap();
+        assert_eq!(exit, FlowExit { output: "/home/docs".to_string(), exit_code: 3 });
+        assert!(testee.exit(Some(&variable_set), None, &Key::Esc).unwrap().is_none());
+        assert_eq!(testee.get_controls(), vec![exit_control]);
+    }
+
+    #[test]
+    fn get_batch_terminal_handover_uses_transition_applicable_to_all_marked_lines() {
+        let activation_control = Control::new("edit", Key::Char('e'));
+        let mut mock_transition = MockTransit::new();
+        mock_transition
+            .expect_get_activation_control()
+            .return_const(activation_control.clone());
+        mock_transition
+            .expect_is_applicable()
+            .returning(|variables| variables.get_value("file").is_some());
+        mock_transition
+            .expect_get_terminal_handover()
+            .return_const(Some(TerminalHandover::Return));
+
+        let testee = WorkflowState::new("some state", VariableInjector::new("".to_string()), vec![mock_transition]);
+        let file: VariableSet = vec![Variable::new("file".into(), "a.txt".to_string())].into_iter().collect();
+        let key = activation_control.get_key();
+        assert_eq!(testee.get_batch_terminal_handover(&[file.clone(), file.clone()], &key), Some(TerminalHandover::Return));
+        assert_eq!(testee.get_batch_terminal_handover(&[file, VariableSet::empty()], &key), None);
+    }
+
+    #[test]
+    fn get_applicable_controls_leaves_out_controls_without_applicable_transition() {
+        let transitions = [(Key::Char('d'), false), (Key::Char('l'), true)]
+            .into_iter()
+            .map(|(key, applicable)| {
+                let mut mock_transition = MockTransit::new();
+                mock_transition
+                    .expect_get_activation_control()
+                    .return_const(Control::new("some control", key));
+                mock_transition
+                    .expect_is_applicable()
+                    .return_const(applicable);
+                mock_transition
+            })
+            .collect();
+
+        let testee = WorkflowState::new("some state", VariableInjector::new("".to_string()), transitions);
+        let applicable_controls = testee.get_applicable_controls(Some(&VariableSet::empty()), None);
+        assert_eq!(applicable_controls, vec![Control::new("some control", Key::Char('l'))]);
+    }
+
+    #[test]
+    fn get_applicable_controls_of_input_state_decides_on_typed_text() {
+        let mut mock_transition = MockTransit::new();
+        mock_transition
+            .expect_get_activation_control()
+            .return_const(Control::new("create", Key::Enter));
+        mock_transition
+            .expect_is_applicable_to_input()
+            .returning(|variables| variables.get_value("name").is_some_and(|name| !name.contains(' ')));
+
+        let input_kind = WorkflowStateKind::Input {
+            prompt: "branch name".to_string(),
+            capture: InputCapture::Variable("name".into()),
+        };
+        let testee = WorkflowState::new("some state", VariableInjector::new("".to_string()), vec![mock_transition])
+            .with_kind(input_kind);
+        assert_eq!(testee.get_applicable_controls(None, Some("feature")), vec![Control::new("create", Key::Enter)]);
+        assert!(testee.get_applicable_controls(None, Some("my feature")).is_empty());
+    }
+}
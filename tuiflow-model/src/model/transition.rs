@@ -3,11 +3,39 @@ use crate::model::variable_mapping::VariableExtractor;
 use crate::state::State;
 use crate::state::Transit;
 use crate::state::WorkflowState;
-use crate::variable_mapping::VariableInjector;
+use crate::variable_mapping::{VariableCondition, VariableInjector};
 use std::{cell::RefCell, rc::Rc};
-use tuiflow_model_contracts::command_runner::CommandRunner;
+use tuiflow_model_contracts::command_runner::{CommandOutput, CommandRunner, CommandRunnerError, ExitStatus};
 use tuiflow_model_contracts::control::Control;
 use tuiflow_model_contracts::error::StateTransitionError;
+use tuiflow_model_contracts::terminal_flow::TerminalHandover;
+
+#[derive(Clone)]
+pub enum EmptyOutputFallback {
+    Command(VariableInjector), // an alternative command whose output is extracted instead
+    Lines(String),             // default lines that are extracted instead
+    Refuse(VariableInjector),  // a message explaining why the transition is refused
+}
+
+#[derive(Clone)]
+pub enum InteractiveMode {
+    Return(VariableInjector), // the command whose output is shown once the interactive program exits
+    Exit,                     // tuiflow exits together with the interactive program
+}
+
+/// How a transition runs for several marked lines at once.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchMode {
+    Each { parallelism: usize }, // the command once per line, at most `parallelism` at a time
+    Joined { separator: String }, // the command once, each variable holding the values of all lines joined
+}
+
+/// An exit of the commands of a transition that leads to another state than its next state.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExitRoute {
+    Code(i32), // the commands exited with exactly this code
+    Failure,   // the commands exited with any other non-zero code or were killed by a signal
+}
 
 pub struct Transition<R: CommandRunner, M: VariableExtractor> {
     control: Control,
@@ -15,19 +43,125 @@ pub struct Transition<R: CommandRunner, M: VariableExtractor> {
     variable_set_command_filler: VariableInjector, // regex extraction from selection
     cli_output_variable_extractor: M,
     command_runner: R,
+    on_empty: Option<EmptyOutputFallback>,
+    interactive: Option<InteractiveMode>,
+    condition: Option<VariableCondition>, // an additional condition on the selected line and its variables
+    batch_mode: BatchMode,
+    confirmation: Option<VariableInjector>, // the question the user answers before the transition runs
+    is_failed_on_non_zero_exit: bool, // a command exiting with a non-zero code fails the transition
+    exit_routes: Vec<(ExitRoute, Rc<RefCell<WorkflowState<Self>>>)>, // states to go to instead for some exits
+    output_routes: Vec<(M, Rc<RefCell<WorkflowState<Self>>>)>, // states to go to instead if their extractor matches
+    is_side_effect: bool, // the transition stays in its next state, the one it belongs to, and builds it again
+    is_output_toasted: bool, // the output of a side effect is shown as toast
 }
 
 impl<R: CommandRunner, M: VariableExtractor> Transit for Transition<R, M> {
     fn run(&self, variables: &VariableSet) -> Result<State<Self>, StateTransitionError> {
-        let transition_command = self
-            .get_transition_command(variables);
+        self.run_batch(std::slice::from_ref(variables))
+    }
+
+    fn rerun(&self, variables: &VariableSet) -> Result<State<Self>, StateTransitionError> {
+        self.rerun_batch(std::slice::from_ref(variables))
+    }
+
+    fn run_batch(&self, variable_sets: &[VariableSet]) -> Result<State<Self>, StateTransitionError> {
+        if let Some(interactive) = &self.interactive {
+            for variables in variable_sets {
+                self.command_runner
+                    .run_interactive_command(&self.get_transition_command(variables))
+                    .map_err(StateTransitionError::CommandExecutionError)?;
+            }
+            if let InteractiveMode::Exit = interactive {
+                return Ok(State::new(Rc::clone(&self.next_state), vec![]));
+            }
+        }
+        self.rerun_batch(variable_sets)
+    }
+
+    fn rerun_batch(&self, variable_sets: &[VariableSet]) -> Result<State<Self>, StateTransitionError> {
+        let entry_variables = self.get_entry_variables(variable_sets);
+        let transition_commands = match (&self.interactive, &self.batch_mode) {
+            (Some(InteractiveMode::Exit), _) => return Ok(State::new(Rc::clone(&self.next_state), vec![])),
+            (Some(InteractiveMode::Return(resume_command_filler)), _) => {
+                vec![resume_command_filler.inject(&entry_variables).into()]
+            }
+            (None, BatchMode::Joined { .. }) => vec![self.get_transition_command(&entry_variables)],
+            (None, BatchMode::Each { .. }) => variable_sets
+                .iter()
+                .map(|variables| self.get_transition_command(variables))
+                .collect(),
+        };
+
+        if !self.is_side_effect && self.next_state.borrow().get_stream().is_some() {
+            let streaming_command = self.get_streaming_script(&transition_commands).into();
+            let output_stream = self
+                .command_runner
+                .run_streaming_command(&streaming_command)
+                .map_err(StateTransitionError::CommandExecutionError)?;
+            return Ok(State::new_streaming(Rc::clone(&self.next_state), output_stream, entry_variables));
+        }
+
+        let output = self.run_commands(&transition_commands)?;
+        if let Some(exit_state) = self.get_exit_state(&output.exit_status) {
+            let cli_result = match output.is_success() {
+                true => output.stdout,
+                false => output.get_stdout_and_stderr(), // a failed command mostly explains why on stderr
+            };
+            let output_variables = self.cli_output_variable_extractor.extract(&cli_result);
+            return Ok(Self::enter(exit_state, &cli_result, output_variables, entry_variables));
+        }
+        // e.g. `kubectl get pods` reports an empty namespace on stderr
+        let route_output = output.get_stdout_and_stderr();
+        for (output_extractor, output_state) in &self.output_routes {
+            let output_variables = output_extractor.extract(&route_output);
+            if !output_variables.is_empty() {
+                return Ok(Self::enter(output_state, &route_output, output_variables, entry_variables));
+            }
+        }
+
+        if self.is_side_effect {
+            let toast = self.is_output_toasted.then(|| Self::get_toast(&output.stdout)).flatten();
+            return Ok(State::new_side_effect(Rc::clone(&self.next_state), toast));
+        }
+        if self.next_state.borrow().is_prose() {
+            return Ok(State::new_prose(Rc::clone(&self.next_state), &output.stdout, entry_variables));
+        }
 
-        self.run_command(&transition_command)
+        let mut output_variables = self.cli_output_variable_extractor.extract(&output.stdout);
+        if output_variables.is_empty() {
+            output_variables = self.run_on_empty(&entry_variables)?;
+        }
+        Ok(State::new(Rc::clone(&self.next_state), output_variables))
+    }
+
+    fn extract(&self, output: &str) -> Vec<VariableSet> {
+        self.cli_output_variable_extractor.extract(output)
     }
 
     fn get_activation_control(&self) -> &Control {
         &self.control
     }
+
+    fn is_applicable(&self, variables: &VariableSet) -> bool {
+        self.cli_output_variable_extractor.matches(variables.get_source_line())
+            && self.condition.as_ref().is_none_or(|condition| condition.is_met(variables))
+    }
+
+    fn is_applicable_to_input(&self, variables: &VariableSet) -> bool {
+        self.condition.as_ref().is_none_or(|condition| condition.is_met(variables))
+    }
+
+    fn get_confirmation(&self, variable_sets: &[VariableSet]) -> Option<String> {
+        let confirmation = self.confirmation.as_ref()?;
+        Some(confirmation.inject(&self.get_entry_variables(variable_sets)))
+    }
+
+    fn get_terminal_handover(&self) -> Option<TerminalHandover> {
+        self.interactive.as_ref().map(|interactive| match interactive {
+            InteractiveMode::Return(_) => TerminalHandover::Return,
+            InteractiveMode::Exit => TerminalHandover::Exit,
+        })
+    }
 }
 
 impl<R: CommandRunner, M: VariableExtractor> Transition<R, M> {
@@ -44,8 +178,124 @@ impl<R: CommandRunner, M: VariableExtractor> Transition<R, M> {
             variable_set_command_filler,
             command_runner,
             cli_output_variable_extractor,
+            on_empty: None,
+            interactive: None,
+            condition: None,
+            batch_mode: BatchMode::Each { parallelism: 1 },
+            confirmation: None,
+            is_failed_on_non_zero_exit: false,
+            exit_routes: vec![],
+            output_routes: vec![],
+            is_side_effect: false,
+            is_output_toasted: false,
         }
     }
+
+    pub fn with_on_empty(mut self, on_empty: EmptyOutputFallback) -> Self {
+        self.on_empty = Some(on_empty);
+        self
+    }
+
+    /// Only applies the transition to lines that meet the condition as well.
+    pub fn with_condition(mut self, condition: VariableCondition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// Runs the command attached to the terminal instead of capturing its output.
+    pub fn with_interactive(mut self, interactive: InteractiveMode) -> Self {
+        self.interactive = Some(interactive);
+        self
+    }
+
+    /// Decides how the transition runs for several marked lines, by default once per line one after another.
+    pub fn with_batch_mode(mut self, batch_mode: BatchMode) -> Self {
+        self.batch_mode = batch_mode;
+        self
+    }
+
+    /// Asks the user the question, filled with the selected variables, before the transition runs.
+    pub fn with_confirmation(mut self, confirmation: VariableInjector) -> Self {
+        self.confirmation = Some(confirmation);
+        self
+    }
+
+    /// Fails the transition when a command exits with a non-zero code instead of using its output anyway.
+    pub fn with_fail_on_non_zero_exit(mut self, is_failed_on_non_zero_exit: bool) -> Self {
+        self.is_failed_on_non_zero_exit = is_failed_on_non_zero_exit;
+        self
+    }
+
+    /// Leads to the state instead of the next state when the commands exit as the route says.
+    /// Routes for exact exit codes take precedence over the failure route.
+    pub fn with_exit_route(mut self, exit_route: ExitRoute, state: Rc<RefCell<WorkflowState<Self>>>) -> Self {
+        self.exit_routes.push((exit_route, state));
+        self
+    }
+
+    /// Leads to the state with the variables of the extractor instead of the next state when the extractor
+    /// extracts any from the output. Routes are tried in the order they were added, before the extractor of the transition.
+    pub fn with_output_route(mut self, output_extractor: M, state: Rc<RefCell<WorkflowState<Self>>>) -> Self {
+        self.output_routes.push((output_extractor, state));
+        self
+    }
+
+    /// Stays in the next state, which has to be the state the transition belongs to, and builds it again
+    /// after the command ran instead of entering it with the output. Routes still lead to their states.
+    pub fn with_side_effect(mut self, is_output_toasted: bool) -> Self {
+        self.is_side_effect = true;
+        self.is_output_toasted = is_output_toasted;
+        self
+    }
+
+    /// The lines of the output in a single line, `None` if there are none.
+    fn get_toast(output: &str) -> Option<String> {
+        let lines = output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        (!lines.is_empty()).then(|| lines.join(" · "))
+    }
+
+    /// The state showing the output, as prose or as the extracted lines.
+    fn enter(
+        state: &Rc<RefCell<WorkflowState<Self>>>,
+        cli_result: &str,
+        output_variables: Vec<VariableSet>,
+        entry_variables: VariableSet,
+    ) -> State<Self> {
+        if state.borrow().is_prose() {
+            return State::new_prose(Rc::clone(state), cli_result, entry_variables);
+        }
+        State::new(Rc::clone(state), output_variables)
+    }
+
+    fn get_exit_state(&self, exit_status: &ExitStatus) -> Option<&Rc<RefCell<WorkflowState<Self>>>> {
+        let find_route = |wanted: &ExitRoute| {
+            self.exit_routes
+                .iter()
+                .find(|(exit_route, _)| exit_route == wanted)
+                .map(|(_, state)| state)
+        };
+        match exit_status {
+            ExitStatus::Code(code) => find_route(&ExitRoute::Code(*code)),
+            ExitStatus::Signal(_) => None,
+        }
+        .or_else(|| match exit_status {
+            ExitStatus::Code(0) => None,
+            _ => find_route(&ExitRoute::Failure),
+        })
+    }
+
+    /// The variables of the lines the transition runs for, joined as the batch mode joins them.
+    fn get_entry_variables(&self, variable_sets: &[VariableSet]) -> VariableSet {
+        match &self.batch_mode {
+            BatchMode::Joined { separator } => VariableSet::joined(variable_sets, separator),
+            BatchMode::Each { .. } => VariableSet::joined(variable_sets, " "),
+        }
+    }
+
     fn get_transition_command(
         &self,
         variables: &VariableSet,
@@ -58,14 +308,108 @@ impl<R: CommandRunner, M: VariableExtractor> Transition<R, M> {
     fn run_command(
         &self,
         command_to_execute: &<R as CommandRunner>::Command,
-    ) -> Result<State<Self>, StateTransitionError> {
-        let cli_result = self
-            .command_runner
-            .run_command(&command_to_execute)
-            .map_err(|e| StateTransitionError::CommandExecutionError(e))?;
+    ) -> Result<String, StateTransitionError> {
+        let output = self.command_runner.run_command(command_to_execute);
+        Ok(self.check_exit(command_to_execute, output)?.stdout)
+    }
+
+    /// The output of the command, or the failure if the command failed or its exit counts as failure.
+    /// A side effect always fails on a non-zero exit, so its stderr is shown instead of a toast of its stdout.
+    /// An exit with a route never counts as failure.
+    fn check_exit(
+        &self,
+        command: &<R as CommandRunner>::Command,
+        output: Result<CommandOutput, CommandRunnerError>,
+    ) -> Result<CommandOutput, StateTransitionError> {
+        match output {
+            Ok(output)
+                if (self.is_failed_on_non_zero_exit || self.is_side_effect)
+                    && !output.is_success()
+                    && self.get_exit_state(&output.exit_status).is_none() =>
+            {
+                Err(StateTransitionError::CommandExecutionError(CommandRunnerError::FailedExit {
+                    command: R::get_full_command(command),
+                    output,
+                }))
+            }
+            Ok(output) => Ok(output),
+            Err(e) => Err(StateTransitionError::CommandExecutionError(e)),
+        }
+    }
+
+    /// The commands as one script for a single stream, in parallel up to the parallelism of the batch mode.
+    /// The lines of parallel commands interleave as they are printed.
+    fn get_streaming_script(&self, commands: &[R::Command]) -> String {
+        let parallelism = match self.batch_mode {
+            BatchMode::Each { parallelism } => parallelism.max(1),
+            BatchMode::Joined { .. } => 1,
+        };
+        commands
+            .chunks(parallelism)
+            .map(|chunk| match chunk {
+                [command] => command.to_string(),
+                _ => {
+                    let background_commands = chunk.iter().map(|command| format!("(\n{}\n) &", &**command));
+                    background_commands.chain(["wait".to_string()]).collect::<Vec<_>>().join("\n")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Runs the commands, in parallel up to the parallelism of the batch mode, and joins their outputs in order.
+    /// The joined output exits as the first command that did not succeed.
+    fn run_commands(&self, commands: &[R::Command]) -> Result<CommandOutput, StateTransitionError> {
+        let parallelism = match self.batch_mode {
+            BatchMode::Each { parallelism } => parallelism.max(1),
+            BatchMode::Joined { .. } => 1,
+        };
+        let command_runner = &self.command_runner;
+        let mut outputs = vec![];
+        for chunk in commands.chunks(parallelism) {
+            let chunk_outputs = match chunk {
+                [command] => vec![command_runner.run_command(command)],
+                _ => command_runner.run_parallel_commands(chunk),
+            };
+            for (command, output) in chunk.iter().zip(chunk_outputs) {
+                outputs.push(self.check_exit(command, output)?);
+            }
+        }
+
+        if let [output] = outputs.as_slice() {
+            return Ok(output.clone());
+        }
+        let join = |texts: Vec<&str>| {
+            texts
+                .into_iter()
+                .map(|text| text.trim_end_matches('\n'))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        Ok(CommandOutput {
+            stdout: join(outputs.iter().map(|output| output.stdout.as_str()).collect()),
+            stderr: join(outputs.iter().map(|output| output.stderr.as_str()).filter(|stderr| !stderr.is_empty()).collect()),
+            exit_status: outputs
+                .iter()
+                .map(|output| output.exit_status)
+                .find(|exit_status| *exit_status != ExitStatus::Code(0))
+                .unwrap_or(ExitStatus::Code(0)),
+            duration: outputs.iter().map(|output| output.duration).sum(),
+        })
+    }
 
-        let variables = self.cli_output_variable_extractor.extract(&cli_result);
-        Ok(State::new(Rc::clone(&self.next_state), variables))
+    fn run_on_empty(&self, variables: &VariableSet) -> Result<Vec<VariableSet>, StateTransitionError> {
+        match &self.on_empty {
+            None => Ok(vec![]),
+            Some(EmptyOutputFallback::Command(command_filler)) => {
+                let cli_result = self.run_command(&command_filler.inject(variables).into())?;
+                Ok(self.cli_output_variable_extractor.extract(&cli_result))
+            }
+            Some(EmptyOutputFallback::Lines(lines)) => Ok(self.cli_output_variable_extractor.extract(lines)),
+            Some(EmptyOutputFallback::Refuse(message_filler)) => {
+                Err(StateTransitionError::EmptyOutput(message_filler.inject(variables)))
+            }
+        }
     }
 }
 
@@ -77,6 +421,24 @@ impl<R: CommandRunner, M: VariableExtractor> Clone for Transition<R, M> {
             variable_set_command_filler: self.variable_set_command_filler.clone(),
             command_runner: self.command_runner.clone(),
             cli_output_variable_extractor: self.cli_output_variable_extractor.clone(),
+            on_empty: self.on_empty.clone(),
+            interactive: self.interactive.clone(),
+            condition: self.condition.clone(),
+            batch_mode: self.batch_mode.clone(),
+            confirmation: self.confirmation.clone(),
+            is_failed_on_non_zero_exit: self.is_failed_on_non_zero_exit,
+            exit_routes: self
+                .exit_routes
+                .iter()
+                .map(|(exit_route, state)| (exit_route.clone(), Rc::clone(state)))
+                .collect(),
+            output_routes: self
+                .output_routes
+                .iter()
+                .map(|(output_extractor, state)| (output_extractor.clone(), Rc::clone(state)))
+                .collect(),
+            is_side_effect: self.is_side_effect,
+            is_output_toasted: self.is_output_toasted,
         }
     }
 }
@@ -85,56 +447,342 @@ impl<R: CommandRunner, M: VariableExtractor> Clone for Transition<R, M> {
 mod test {
     use std::cell::RefCell;
     use std::rc::Rc;
-    use tuiflow_model_contracts::command_runner::MockCommand;
-    use tuiflow_model_contracts::command_runner::MockCommandRunner;
+    use tuiflow_model_contracts::command_runner::{CommandOutput, CommandRunnerError, ExitStatus, MockCommandRunner};
+    use std::time::Duration;
     use tuiflow_model_contracts::control::{Control, Key};
-    use crate::model::variable::VariableSet;
-    use crate::state::{Transit, WorkflowState};
-    use crate::transition::Transition;
-    use crate::variable_mapping::{MockVariableExtractor, VariableInjector};
+    use mockall::Sequence;
+    use tuiflow_model_contracts::error::StateTransitionError;
+    use crate::model::variable::{Variable, VariableSet};
+    use crate::state::{StreamSettings, Transit, WorkflowState, WorkflowStateKind};
+    use std::sync::mpsc;
+    use tuiflow_model_contracts::output_stream::OutputStream;
+    use crate::transition::{BatchMode, EmptyOutputFallback, ExitRoute, InteractiveMode, Transition};
+    use tuiflow_model_contracts::terminal_flow::TerminalHandover;
+    use crate::variable_mapping::{MockVariableExtractor, RegexVariableExtractor, VariableCondition, VariableExtractor, VariableInjector};
+    use regex::Regex;
 
-    #[test]
-    fn run_runs_command_returned_by_variable_filler() {
-        let expected_command = MockCommand::from("rm all_and_everything".to_string());
-        let variable_injector = VariableInjector::new(expected_command.command.clone());
-        let workflow_state = Rc::new(RefCell::new(WorkflowState::new("state", variable_injector.clone(), vec![])));
+    type TestState<M> = Rc<RefCell<WorkflowState<Transition<MockCommandRunner, M>>>>;
+
+    fn state<M: VariableExtractor>(name: &str) -> TestState<M> {
+        Rc::new(RefCell::new(WorkflowState::new(name, VariableInjector::new("<x>".to_string()), vec![])))
+    }
+
+    /// A runner expecting to run the command once, printing the stdout.
+    fn runner_running(command: &'static str, stdout: &'static str) -> MockCommandRunner {
         let mut command_runner = MockCommandRunner::default();
         command_runner
             .expect_run_command()
             .once()
-            .withf(move |cmd| cmd.command == expected_command.command)
-            .returning(|_| Ok("and there was nothing".to_string()));
+            .withf(move |cmd| cmd.command == command)
+            .returning(move |_| Ok(CommandOutput::from_stdout(stdout.to_string())));
+        command_runner
+    }
+
+    /// A runner expecting to run once, whatever the command.
+    fn runner_returning(output: CommandOutput) -> MockCommandRunner {
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_command()
+            .once()
+            .returning(move |_| Ok(output.clone()));
+        command_runner
+    }
+
+    /// A transition into the state whose lines are the lines of the output.
+    fn transition(
+        to: &TestState<RegexVariableExtractor>,
+        command: &str,
+        command_runner: MockCommandRunner,
+    ) -> Transition<MockCommandRunner, RegexVariableExtractor> {
+        Transition::new(
+            Control::new("ctrl", Key::Esc),
+            Rc::clone(to),
+            VariableInjector::new(command.to_string()),
+            command_runner,
+            RegexVariableExtractor::new("(?<x>.+)").unwrap(),
+        )
+    }
+
+    #[test]
+    fn run_runs_command_returned_by_variable_filler() {
+        let command_runner = runner_running("rm all_and_everything", "and there was nothing");
         let mut variable_extractor = MockVariableExtractor::new();
         variable_extractor
             .expect_extract()
             .once()
             .returning(|_| vec![]);
-        let transition = Transition::new(Control::new("ctrl", Key::Esc), workflow_state.clone(), variable_injector, command_runner, variable_extractor);
+        let transition = Transition::new(Control::new("ctrl", Key::Esc), state("state"), VariableInjector::new("rm all_and_everything".to_string()), command_runner, variable_extractor);
         let variable_set = VariableSet::empty();
         _ = transition.run(&variable_set);
     }
     
     #[test]
     fn run_extracts_variables_from_command_result() {
-        let expected_command = MockCommand::from("rm all_and_everything".to_string());
-        let variable_injector = VariableInjector::new(expected_command.command.clone());
-        let workflow_state = Rc::new(RefCell::new(WorkflowState::new("state", variable_injector.clone(), vec![])));
         let cli_output = "and there was nothing";
-        let mut command_runner = MockCommandRunner::default();
-        command_runner
-            .expect_run_command()
-            .once()
-            .withf(move |cmd| cmd.command == expected_command.command)
-            .returning(|_| Ok(cli_output.to_string()));
+        let command_runner = runner_running("rm all_and_everything", cli_output);
         let mut variable_extractor = MockVariableExtractor::new();
         variable_extractor
             .expect_extract()
             .once()
             .withf(move |input| input == cli_output)
             .returning(|_| vec![]);
-        let transition = Transition::new(Control::new("ctrl", Key::Esc), workflow_state.clone(), variable_injector, command_runner, variable_extractor);
+        let transition = Transition::new(Control::new("ctrl", Key::Esc), state("state"), VariableInjector::new("rm all_and_everything".to_string()), command_runner, variable_extractor);
         let variable_set = VariableSet::empty();
         _ = transition.run(&variable_set);
 
     }
+
+    #[test]
+    fn run_fails_on_non_zero_exit_only_if_the_transition_says_so_or_runs_for_its_side_effect() {
+        let failed_output = CommandOutput {
+            stdout: "".to_string(),
+            stderr: "no such file".to_string(),
+            exit_status: ExitStatus::Code(2),
+            duration: Duration::from_millis(20),
+        };
+        let build_transition = |is_failed_on_non_zero_exit: bool, is_side_effect: bool| {
+            let transition = transition(&state("state"), "cat missing", runner_returning(failed_output.clone()))
+                .with_fail_on_non_zero_exit(is_failed_on_non_zero_exit);
+            if is_side_effect {
+                transition.with_side_effect(true)
+            } else {
+                transition
+            }
+        };
+
+        assert!(build_transition(false, false).run(&VariableSet::empty()).is_ok());
+        for (is_failed_on_non_zero_exit, is_side_effect) in [(true, false), (false, true)] {
+            match build_transition(is_failed_on_non_zero_exit, is_side_effect).run(&VariableSet::empty()) {
+                Err(StateTransitionError::CommandExecutionError(error)) => assert_eq!(
+                    error,
+                    CommandRunnerError::FailedExit { command: "cat missing".to_string(), output: failed_output.clone() }
+                ),
+                _ => panic!("expected the non-zero exit to fail the transition"),
+            }
+        }
+    }
+
+    #[test]
+    fn run_routes_by_exit_code_before_failure_and_extracts_stderr_of_failed_commands() {
+        let run_with_exit_code = |exit_code: i32| {
+            let command_runner = runner_returning(CommandOutput {
+                stdout: "main\n".to_string(),
+                stderr: if exit_code == 0 { "".to_string() } else { "error: conflict\n".to_string() },
+                exit_status: ExitStatus::Code(exit_code),
+                duration: Duration::ZERO,
+            });
+            transition(&state("branches"), "git checkout main", command_runner)
+                .with_fail_on_non_zero_exit(true)
+                .with_exit_route(ExitRoute::Failure, state("conflicts"))
+                .with_exit_route(ExitRoute::Code(128), state("missing"))
+                .run(&VariableSet::empty())
+                .ok()
+                .unwrap()
+        };
+
+        let state = run_with_exit_code(0);
+        assert_eq!(state.get_name(), "branches");
+        assert_eq!(state.get_display().lines, vec!["main".into()]);
+        let state = run_with_exit_code(1);
+        assert_eq!(state.get_name(), "conflicts");
+        assert_eq!(state.get_display().lines, vec!["main".into(), "error: conflict".into()]);
+        assert_eq!(run_with_exit_code(128).get_name(), "missing");
+    }
+
+    #[test]
+    fn run_enters_state_of_first_output_route_that_extracts_variables() {
+        let run_with_output = |stdout: &'static str, stderr: &'static str| {
+            let command_runner = runner_returning(CommandOutput { stderr: stderr.to_string(), ..CommandOutput::from_stdout(stdout.to_string()) });
+            let variable_injector = VariableInjector::new("kubectl get pods".to_string());
+            Transition::new(Control::new("ctrl", Key::Esc), state("pods"), variable_injector, command_runner, RegexVariableExtractor::new("(?<x>\\S+) Running").unwrap())
+                .with_output_route(RegexVariableExtractor::new("(?<x>No resources found.*)").unwrap(), state("empty"))
+                .with_output_route(RegexVariableExtractor::new("(?<x>.*refused.*)").unwrap(), state("unreachable"))
+                .run(&VariableSet::empty())
+                .ok()
+                .unwrap()
+        };
+
+        let state = run_with_output("api-1 Running\nweb-2 Running\n", "");
+        assert_eq!(state.get_name(), "pods");
+        assert_eq!(state.get_display().lines, vec!["api-1".into(), "web-2".into()]);
+        let state = run_with_output("", "No resources found in default namespace.\n");
+        assert_eq!(state.get_name(), "empty");
+        assert_eq!(state.get_display().lines, vec!["No resources found in default namespace.".into()]);
+        assert_eq!(run_with_output("", "connection refused\n").get_name(), "unreachable");
+    }
+
+    #[test]
+    fn run_with_empty_output_runs_fallback_command() {
+        let mut sequence = Sequence::new();
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_command()
+            .once()
+            .in_sequence(&mut sequence)
+            .withf(|cmd| cmd.command == "ls empty_dir")
+            .returning(|_| Ok(CommandOutput::from_stdout("".to_string())));
+        command_runner
+            .expect_run_command()
+            .once()
+            .in_sequence(&mut sequence)
+            .withf(|cmd| cmd.command == "ls ..")
+            .returning(|_| Ok(CommandOutput::from_stdout("parent_dir".to_string())));
+        let transition = transition(&state("state"), "ls empty_dir", command_runner)
+            .with_on_empty(EmptyOutputFallback::Command(VariableInjector::new("ls ..".to_string())));
+
+        let next_state = transition.run(&VariableSet::empty()).unwrap();
+        assert_eq!(next_state.get_display().lines.len(), 1);
+    }
+
+    #[test]
+    fn run_with_empty_output_extracts_fallback_lines() {
+        let transition = transition(&state("state"), "ls", runner_returning(CommandOutput::from_stdout("".to_string())))
+            .with_on_empty(EmptyOutputFallback::Lines("nothing here\ngo back".to_string()));
+
+        let next_state = transition.run(&VariableSet::empty()).unwrap();
+        assert_eq!(next_state.get_display().lines, vec!["nothing here".into(), "go back".into()]);
+    }
+
+    #[test]
+    fn run_with_empty_output_and_refusing_fallback_returns_message() {
+        let transition = transition(&state("state"), "ls <path>", runner_returning(CommandOutput::from_stdout("".to_string())))
+            .with_on_empty(EmptyOutputFallback::Refuse(VariableInjector::new("<path> is empty".to_string())));
+
+        let variable_set = vec![Variable::new("path".into(), "/tmp".to_string())].into_iter().collect();
+        let result = transition.run(&variable_set);
+        assert!(matches!(result, Err(StateTransitionError::EmptyOutput(message)) if message == "/tmp is empty"));
+    }
+
+    #[test]
+    fn run_into_prose_state_displays_output_as_is_and_keeps_entry_variables() {
+        let prose_state = WorkflowState::new("show commit", VariableInjector::new("".to_string()), vec![])
+            .with_kind(WorkflowStateKind::Prose { markdown: false });
+        let command_runner = runner_running("git show 1a2b", "commit 1a2b\n\n    fix everything");
+        let mut variable_extractor = MockVariableExtractor::new();
+        variable_extractor
+            .expect_extract()
+            .never();
+        let transition = Transition::new(Control::new("ctrl", Key::Esc), Rc::new(RefCell::new(prose_state)), VariableInjector::new("git show <sha>".to_string()), command_runner, variable_extractor);
+
+        let variable_set: VariableSet = vec![Variable::new("sha".into(), "1a2b".to_string())].into_iter().collect();
+        let next_state = transition.run(&variable_set).unwrap();
+        assert_eq!(next_state.get_display().lines, vec!["commit 1a2b".into(), "".into(), "    fix everything".into()]);
+    }
+
+    #[test]
+    fn run_interactive_returning_runs_program_then_resume_command() {
+        let mut sequence = Sequence::new();
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_interactive_command()
+            .once()
+            .in_sequence(&mut sequence)
+            .withf(|cmd| cmd.command == "vi notes.txt")
+            .returning(|_| Ok(()));
+        command_runner
+            .expect_run_command()
+            .once()
+            .in_sequence(&mut sequence)
+            .withf(|cmd| cmd.command == "ls")
+            .returning(|_| Ok(CommandOutput::from_stdout("notes.txt".to_string())));
+        let transition = transition(&state("state"), "vi <x>", command_runner)
+            .with_interactive(InteractiveMode::Return(VariableInjector::new("ls".to_string())));
+
+        let variable_set = vec![Variable::new("x".into(), "notes.txt".to_string())].into_iter().collect();
+        let next_state = transition.run(&variable_set).unwrap();
+        assert_eq!(next_state.get_display().lines, vec!["notes.txt".into()]);
+        assert_eq!(transition.get_terminal_handover(), Some(TerminalHandover::Return));
+    }
+
+    #[test]
+    fn run_interactive_exiting_runs_no_further_command() {
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_interactive_command()
+            .once()
+            .returning(|_| Ok(()));
+        command_runner
+            .expect_run_command()
+            .never();
+        let transition = transition(&state("state"), "vi", command_runner).with_interactive(InteractiveMode::Exit);
+
+        assert!(transition.run(&VariableSet::empty()).is_ok());
+        assert_eq!(transition.get_terminal_handover(), Some(TerminalHandover::Exit));
+    }
+
+    #[test]
+    fn is_applicable_with_line_condition_requires_line_to_match() {
+        let transition = transition(&state("state"), "kubectl delete pod <x>", MockCommandRunner::default())
+            .with_condition(VariableCondition::LineMatches(Regex::new("CrashLoopBackOff$").unwrap()));
+
+        let crashing = VariableSet::empty().with_source_line("api-1 CrashLoopBackOff".to_string());
+        let running = VariableSet::empty().with_source_line("api-2 Running".to_string());
+        assert!(transition.is_applicable(&crashing));
+        assert!(!transition.is_applicable(&running));
+    }
+
+    fn file_lines(files: &[&str]) -> Vec<VariableSet> {
+        files
+            .iter()
+            .map(|file| {
+                let variable_set: VariableSet = vec![Variable::new("path".into(), file.to_string())].into_iter().collect();
+                variable_set.with_source_line(file.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn run_batch_joined_runs_command_once_with_joined_values() {
+        let transition = transition(&state("state"), "rm <path>", runner_running("rm a.txt b.txt", "removed"))
+            .with_batch_mode(BatchMode::Joined { separator: " ".to_string() });
+
+        let next_state = transition.run_batch(&file_lines(&["a.txt", "b.txt"])).unwrap();
+        assert_eq!(next_state.get_display().lines, vec!["removed".into()]);
+    }
+
+    #[test]
+    fn run_batch_each_runs_command_per_line_and_keeps_outputs_in_order() {
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_command()
+            .times(3)
+            .returning(|cmd| {
+                let pod = cmd.command.trim_start_matches("kubectl rollout restart ");
+                Ok(CommandOutput::from_stdout(format!("{pod} restarted\n")))
+            });
+        let transition = transition(&state("state"), "kubectl rollout restart <path>", command_runner)
+            .with_batch_mode(BatchMode::Each { parallelism: 2 });
+
+        let next_state = transition.run_batch(&file_lines(&["api", "web", "db"])).unwrap();
+        assert_eq!(
+            next_state.get_display().lines,
+            vec!["api restarted".into(), "web restarted".into(), "db restarted".into()]
+        );
+    }
+
+    #[test]
+    fn run_batch_into_stream_runs_commands_in_parallel_chunks() {
+        let logs = WorkflowState::new("logs", VariableInjector::new("<x>".to_string()), vec![])
+            .with_stream(StreamSettings { max_lines: 100, follow: true });
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_streaming_command()
+            .withf(|cmd| cmd.command == "(\nkubectl logs -f api\n) &\n(\nkubectl logs -f web\n) &\nwait\nkubectl logs -f db")
+            .times(1)
+            .returning(|_| Ok(OutputStream::new(mpsc::channel().1, || {})));
+        let transition = transition(&Rc::new(RefCell::new(logs)), "kubectl logs -f <path>", command_runner)
+            .with_batch_mode(BatchMode::Each { parallelism: 2 });
+
+        assert!(transition.run_batch(&file_lines(&["api", "web", "db"])).is_ok());
+    }
+
+    #[test]
+    fn get_confirmation_fills_message_with_variables_of_all_lines() {
+        let transition = transition(&state("state"), "rm <path>", MockCommandRunner::default());
+        assert_eq!(transition.get_confirmation(&file_lines(&["a.txt"])), None);
+
+        let transition = transition.with_confirmation(VariableInjector::new("Delete <path>?".to_string()));
+        assert_eq!(transition.get_confirmation(&file_lines(&["a.txt"])), Some("Delete a.txt?".to_string()));
+        assert_eq!(transition.get_confirmation(&file_lines(&["a.txt", "b.txt"])), Some("Delete a.txt b.txt?".to_string()));
+    }
 }
@@ -37,11 +37,87 @@ impl From<&str> for VariableName {
 }
 
 #[derive(Clone, Debug)]
-pub struct VariableSet(HashSet<Variable>);
+pub struct VariableSet {
+    variables: HashSet<Variable>,
+    source_line: String, // the whole line of output the variables were extracted from
+}
 
 impl VariableSet {
     pub fn empty() -> Self {
-        Self(HashSet::new())
+        Self {
+            variables: HashSet::new(),
+            source_line: String::new(),
+        }
+    }
+
+    pub fn with_source_line(mut self, source_line: String) -> Self {
+        self.source_line = source_line;
+        self
+    }
+
+    pub fn get_source_line(&self) -> &str {
+        &self.source_line
+    }
+
+    pub fn get_value(&self, name: &str) -> Option<&str> {
+        self.variables
+            .iter()
+            .find(|variable| *variable.name == *name)
+            .map(|variable| variable.value.as_str())
+    }
+
+    /// The value of the key variable, or all variables if there is none, to find the item of the set
+    /// among the sets of a refreshed output.
+    pub fn get_item_key(&self, key_variable: Option<&str>) -> String {
+        if let Some(value) = key_variable.and_then(|key_variable| self.get_value(key_variable)) {
+            return value.to_string();
+        }
+        let mut variables = self
+            .variables
+            .iter()
+            .map(|variable| format!("{}={}", &*variable.name, variable.value))
+            .collect::<Vec<_>>();
+        variables.sort();
+        variables.join("\n")
+    }
+
+    /// Merges both sets, where variables and the source line of `other` take precedence.
+    pub fn merged_with(&self, other: &VariableSet) -> Self {
+        let mut variables = self
+            .variables
+            .iter()
+            .filter(|variable| !other.iter().any(|o| o.name == variable.name))
+            .cloned()
+            .collect::<HashSet<Variable>>();
+        variables.extend(other.variables.iter().cloned());
+        Self {
+            variables,
+            source_line: other.source_line.clone(),
+        }
+    }
+
+    /// Joins the values of each variable over all sets with the separator, e.g. the files of several lines
+    /// into one `rm` command. Variables missing in a set are left out of it, the source line is the first one.
+    pub fn joined(variable_sets: &[VariableSet], separator: &str) -> Self {
+        let Some(first) = variable_sets.first() else {
+            return Self::empty();
+        };
+        let variables = first
+            .variables
+            .iter()
+            .map(|variable| {
+                let values = variable_sets
+                    .iter()
+                    .filter_map(|set| set.iter().find(|v| v.name == variable.name))
+                    .map(|v| v.value.as_str())
+                    .collect::<Vec<_>>();
+                Variable::new(variable.name.clone(), values.join(separator))
+            })
+            .collect();
+        Self {
+            variables,
+            source_line: first.source_line.clone(),
+        }
     }
 }
 
@@ -49,12 +125,15 @@ impl Deref for VariableSet {
     type Target = HashSet<Variable>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.variables
     }
 }
 
 impl FromIterator<Variable> for VariableSet {
     fn from_iter<T: IntoIterator<Item = Variable>>(iter: T) -> Self {
-        Self(iter.into_iter().collect())
+        Self {
+            variables: iter.into_iter().collect(),
+            source_line: String::new(),
+        }
     }
 }
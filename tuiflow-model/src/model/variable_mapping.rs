@@ -1,13 +1,16 @@
+mod variable_condition;
 mod variable_extractor;
 mod variable_injector;
 
 use crate::model::variable::VariableSet;
 use mockall::mock;
+pub use variable_condition::{Comparison, VariableCondition};
 pub use variable_extractor::RegexVariableExtractor;
 pub use variable_injector::VariableInjector;
 
 pub trait VariableExtractor: Clone {
     fn extract(&self, input: &str) -> Vec<VariableSet>;
+    fn matches(&self, input: &str) -> bool;
 }
 
 mock! {
@@ -19,5 +22,6 @@ mock! {
     
     impl VariableExtractor for VariableExtractor {
         fn extract(&self, input: &str) -> Vec<VariableSet>;
+        fn matches(&self, input: &str) -> bool;
     }
 }
\ No newline at end of file
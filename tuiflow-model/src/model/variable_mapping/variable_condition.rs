@@ -0,0 +1,99 @@
+use crate::model::variable::{VariableName, VariableSet};
+use regex::Regex;
+
+/// A condition on the variables of a line, e.g. that a transition only applies to crashing pods.
+#[derive(Clone, Debug)]
+pub enum VariableCondition {
+    Equals { variable: VariableName, value: String },
+    Matches { variable: VariableName, regex: Regex },
+    LineMatches(Regex), // the whole line the variables were extracted from, or the typed text of an input state
+    Compare { variable: VariableName, comparison: Comparison, number: f64 }, // unmet if the value is no number
+    Exists(VariableName),
+    NotExists(VariableName),
+    And(Vec<VariableCondition>),
+    Or(Vec<VariableCondition>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl VariableCondition {
+    pub fn is_met(&self, variables: &VariableSet) -> bool {
+        match self {
+            VariableCondition::Equals { variable, value } => variables.get_value(variable) == Some(value.as_str()),
+            VariableCondition::Matches { variable, regex } => {
+                variables.get_value(variable).is_some_and(|value| regex.is_match(value))
+            }
+            VariableCondition::LineMatches(regex) => regex.is_match(variables.get_source_line()),
+            VariableCondition::Compare { variable, comparison, number } => variables
+                .get_value(variable)
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .is_some_and(|value| match comparison {
+                    Comparison::Less => value < *number,
+                    Comparison::LessOrEqual => value <= *number,
+                    Comparison::Greater => value > *number,
+                    Comparison::GreaterOrEqual => value >= *number,
+                }),
+            VariableCondition::Exists(variable) => variables.get_value(variable).is_some(),
+            VariableCondition::NotExists(variable) => variables.get_value(variable).is_none(),
+            VariableCondition::And(conditions) => conditions.iter().all(|condition| condition.is_met(variables)),
+            VariableCondition::Or(conditions) => conditions.iter().any(|condition| condition.is_met(variables)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::variable::{Variable, VariableSet};
+    use crate::variable_mapping::{Comparison, VariableCondition};
+    use regex::Regex;
+
+    fn pod(status: &str, restarts: &str) -> VariableSet {
+        vec![
+            Variable::new("status".into(), status.to_string()),
+            Variable::new("restarts".into(), restarts.to_string()),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn is_met_combines_conditions_with_and_and_or() {
+        let condition = VariableCondition::And(vec![
+            VariableCondition::Exists("status".into()),
+            VariableCondition::NotExists("deleted".into()),
+            VariableCondition::Or(vec![
+                VariableCondition::Equals { variable: "status".into(), value: "CrashLoopBackOff".to_string() },
+                VariableCondition::Compare { variable: "restarts".into(), comparison: Comparison::Greater, number: 3.0 },
+            ]),
+        ]);
+
+        assert!(condition.is_met(&pod("CrashLoopBackOff", "0")));
+        assert!(condition.is_met(&pod("Running", "4")));
+        assert!(!condition.is_met(&pod("Running", "3")));
+        assert!(!condition.is_met(&pod("Running", "many")));
+        assert!(!condition.is_met(&VariableSet::empty()));
+    }
+
+    #[test]
+    fn is_met_with_regex_requires_variable_to_match() {
+        let condition = VariableCondition::Matches { variable: "status".into(), regex: Regex::new("^Err").unwrap() };
+
+        assert!(condition.is_met(&pod("ErrImagePull", "0")));
+        assert!(!condition.is_met(&pod("Running", "0")));
+        assert!(!condition.is_met(&VariableSet::empty()));
+    }
+
+    #[test]
+    fn is_met_with_line_regex_requires_source_line_to_match() {
+        let condition = VariableCondition::LineMatches(Regex::new("CrashLoopBackOff$").unwrap());
+
+        assert!(condition.is_met(&pod("CrashLoopBackOff", "0").with_source_line("api-1 CrashLoopBackOff".to_string())));
+        assert!(!condition.is_met(&pod("Running", "0").with_source_line("api-2 Running".to_string())));
+    }
+}
@@ -20,6 +20,16 @@ impl RegexVariableExtractor {
             Err(_) => Err(VariableExtractorCompilationError(input_filter_regex.to_string())),
         }
     }
+
+    /// The whole lines of the input the match spans, not only the matched part of them.
+    fn get_source_line<'a>(input: &'a str, captures_of_match: &Captures) -> &'a str {
+        let Some(matched) = captures_of_match.get(0) else {
+            return "";
+        };
+        let line_start = input[..matched.start()].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = input[matched.end()..].find('\n').map_or(input.len(), |idx| matched.end() + idx);
+        input[line_start..line_end].trim_end_matches('\r')
+    }
 }
 
 impl VariableExtractor for RegexVariableExtractor {
@@ -42,9 +52,14 @@ impl VariableExtractor for RegexVariableExtractor {
                         None
                     })
                     .collect::<VariableSet>()
+                    .with_source_line(Self::get_source_line(input, &captures_of_match).to_string())
             })
             .collect::<Vec<VariableSet>>()
     }
+
+    fn matches(&self, input: &str) -> bool {
+        self.input_filter.is_match(input)
+    }
 }
 
 #[cfg(test)]
@@ -80,6 +95,35 @@ mod test {
         assert_eq!(variables_sets.len(), 1);
     }
 
+    #[test]
+    fn extract_keeps_matched_line_as_source_line() {
+        let extractor = RegexVariableExtractor::new(FILE_NAME_EXTRACTOR_EXPRESSION)
+            .unwrap();
+        let file_paths = "/home/user/test.png\n/home/test.txt";
+
+        let variables_sets = extractor.extract(file_paths);
+        assert_eq!(variables_sets[0].get_source_line(), "/home/user/test.png");
+        assert_eq!(variables_sets[1].get_source_line(), "/home/test.txt");
+    }
+
+    #[test]
+    fn extract_keeps_whole_line_as_source_line_if_only_part_matches() {
+        let extractor = RegexVariableExtractor::new("(?<pod>api-\\S+)").unwrap();
+
+        let variables_sets = extractor.extract("NAME STATUS\napi-1 Running\nweb-1 Running");
+        assert_eq!(variables_sets.len(), 1);
+        assert_eq!(variables_sets[0].get_source_line(), "api-1 Running");
+    }
+
+    #[test]
+    fn matches_with_unmatching_line_returns_false() {
+        let extractor = RegexVariableExtractor::new(FILE_NAME_EXTRACTOR_EXPRESSION)
+            .unwrap();
+
+        assert!(extractor.matches("/home/user/test.png"));
+        assert!(!extractor.matches("/home/test,txt"));
+    }
+
     #[test]
     fn extract_with_empty_input_returns_empty_list() {
         let extractor = RegexVariableExtractor::new(FILE_NAME_EXTRACTOR_EXPRESSION)
@@ -1,32 +1,150 @@
-use crate::model::variable::{Variable, VariableSet};
-use crate::state::{State, Transit, WorkflowState};
+use crate::model::variable::VariableSet;
+use crate::state::{AutoTransitionStatus, HeldControl, State, Transit, WorkflowState};
 use tuiflow_model_contracts::control::{Control, Key};
 use tuiflow_model_contracts::display;
 use tuiflow_model_contracts::error::{InitialTransitionError, StateTransitionError};
-use tuiflow_model_contracts::terminal_flow::TerminalFlow;
+use tuiflow_model_contracts::output_stream::StreamUpdate;
+use tuiflow_model_contracts::state_kind::StateKind;
+use tuiflow_model_contracts::terminal_flow::{ControlOutcome, FlowExit, TerminalFlow, TerminalHandover};
+
+const MAX_CHAINED_AUTO_TRANSITIONS: usize = 16;
+const MAX_HISTORY_LENGTH: usize = 100;
 
 pub struct Workflow<T: Transit> {
     current_state: State<T>,
+    history: Vec<HistoryEntry<T>>, // the previous states, the most recent last
     app_title: String,
+    exit: Option<FlowExit>,
+    is_auto_transition_pending: bool, // the auto transition of the current state has not been tried yet
+    background_errors: Vec<StateTransitionError>, // failures of transitions that ran without a key press
+    held_control: Option<HeldControl>, // the control waiting for its confirmation
+}
+
+struct HistoryEntry<T: Transit> {
+    state: State<T>,
+    display_selection_index: Option<usize>, // the selection when the state was left
 }
 
 impl<T: Transit> Workflow<T> {
     pub fn new(
         initializer_state: WorkflowState<T>,
         app_title: String,
+        initial_variables: VariableSet,
     ) -> Result<Self, InitialTransitionError> {
         let init_control = initializer_state
             .get_controls()
             .pop()
             .expect("Initializer state must contain at least one control. Please report this issue on github.");
-        let empty_variable_set: VariableSet = Vec::<Variable>::new().into_iter().collect();
         let current_state = initializer_state
-            .transition(&empty_variable_set, &init_control.get_key())
-            .map_err(|e| InitialTransitionError::from(e))?;
-        Ok(Self {
+            .transition_without_selection(&initial_variables, &init_control.get_key())
+            .map_err(InitialTransitionError::from)?;
+        Ok(Self::from_state(current_state, app_title))
+    }
+
+    /// Starts the workflow in an already built state instead of running an initial transition.
+    pub fn from_state(current_state: State<T>, app_title: String) -> Self {
+        let mut workflow = Self {
             current_state,
+            history: vec![],
             app_title,
-        })
+            exit: None,
+            is_auto_transition_pending: true,
+            background_errors: vec![],
+            held_control: None,
+        };
+        workflow.run_auto_transitions();
+        workflow
+    }
+
+    /// Reports the error like a failure of a transition that ran without a key press, e.g. why the flow has no lines.
+    pub fn with_background_error(mut self, error: StateTransitionError) -> Self {
+        self.background_errors.push(error);
+        self
+    }
+
+    fn enter_state(&mut self, mut next_state: State<T>, display_selection_index: Option<usize>) -> ControlOutcome {
+        if let Some(side_effect) = next_state.take_side_effect() {
+            self.refresh_current_state();
+            return ControlOutcome::Refreshed { toast: side_effect.toast };
+        }
+        self.push_history(next_state, display_selection_index);
+        self.is_auto_transition_pending = true;
+        self.run_auto_transitions();
+        ControlOutcome::Transitioned
+    }
+
+    /// Builds the current state again after a side effect, a state that is not refreshable stays as it is.
+    fn refresh_current_state(&mut self) {
+        match self.current_state.refresh() {
+            Some(Ok(refreshed_state)) => self.replace_current_state(refreshed_state),
+            // the side effect did happen, only the lines are outdated
+            Some(Err(e)) => self.background_errors.push(e),
+            None => {}
+        }
+    }
+
+    /// Shows the state built again instead of the current one, without remembering the current one.
+    fn replace_current_state(&mut self, refreshed_state: State<T>) {
+        let mut previous_state = std::mem::replace(&mut self.current_state, refreshed_state);
+        previous_state.stop_stream();
+    }
+
+    /// Makes the next state the current one and remembers the left state, forgetting the oldest beyond a limit.
+    fn push_history(&mut self, next_state: State<T>, display_selection_index: Option<usize>) {
+        let mut previous_state = std::mem::replace(&mut self.current_state, next_state);
+        previous_state.stop_stream();
+        self.history.push(HistoryEntry {
+            state: previous_state,
+            display_selection_index,
+        });
+        if self.history.len() > MAX_HISTORY_LENGTH {
+            self.history.remove(0);
+        }
+    }
+
+    /// Chains the due auto transitions. A failing auto transition leaves the flow in its state and after a limit
+    /// the chain stops, so that a cycle of auto states cannot spin forever.
+    fn run_auto_transitions(&mut self) -> bool {
+        let mut has_transitioned = false;
+        for _ in 0..MAX_CHAINED_AUTO_TRANSITIONS {
+            if !self.is_auto_transition_pending {
+                return has_transitioned;
+            }
+            match self.current_state.get_auto_transition_status() {
+                AutoTransitionStatus::Waiting => return has_transitioned,
+                AutoTransitionStatus::Never => self.is_auto_transition_pending = false,
+                AutoTransitionStatus::Due(key) => {
+                    self.is_auto_transition_pending = false;
+                    let first_line_index = (!self.current_state.get_display().lines.is_empty()).then_some(0);
+                    // the exit control of a terminal state ends the flow as if it was pressed
+                    match self.current_state.exit(first_line_index, None, &key) {
+                        Ok(Some(exit)) => {
+                            self.exit = Some(exit);
+                            return true;
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            self.background_errors.push(e);
+                            continue;
+                        }
+                    }
+                    match self.current_state.transition(first_line_index, &key) {
+                        Ok(next_state) if next_state.is_side_effect() => {
+                            self.refresh_current_state();
+                            has_transitioned = true;
+                        }
+                        Ok(next_state) => {
+                            self.push_history(next_state, first_line_index);
+                            self.is_auto_transition_pending = true;
+                            has_transitioned = true;
+                        }
+                        Err(e) => self.background_errors.push(e),
+                    }
+                }
+            }
+        }
+        self.is_auto_transition_pending = false;
+        has_transitioned
     }
 }
 
@@ -35,21 +153,99 @@ impl<T: Transit> TerminalFlow for Workflow<T> {
         &mut self,
         display_selection_index: Option<usize>,
         key: &Key,
-    ) -> Result<(), StateTransitionError> {
+    ) -> Result<ControlOutcome, StateTransitionError> {
+        if let Some(exit) = self.current_state.exit(display_selection_index, None, key)? {
+            self.exit = Some(exit);
+            return Ok(ControlOutcome::Transitioned);
+        }
+
         let transition_result: Result<State<T>, StateTransitionError>;
         {
             transition_result = self.current_state.transition(display_selection_index, key);
         }
 
         match transition_result {
-            Ok(next_state) => {
-                self.current_state = next_state;
-                Ok(())
-            }
+            Ok(next_state) => Ok(self.enter_state(next_state, display_selection_index)),
             Err(e) => Err(e),
         }
     }
 
+    fn run_control_on_marked(
+        &mut self,
+        marked_display_indices: &[usize],
+        key: &Key,
+    ) -> Result<ControlOutcome, StateTransitionError> {
+        if let Some(exit) = self.current_state.exit_marked(marked_display_indices, key)? {
+            self.exit = Some(exit);
+            return Ok(ControlOutcome::Transitioned);
+        }
+
+        let next_state = self
+            .current_state
+            .transition_marked(marked_display_indices, key)?;
+        Ok(self.enter_state(next_state, marked_display_indices.first().copied()))
+    }
+
+    fn run_control_with_input(
+        &mut self,
+        display_selection_index: Option<usize>,
+        input: &str,
+        key: &Key,
+    ) -> Result<ControlOutcome, StateTransitionError> {
+        if let Some(exit) = self.current_state.exit(display_selection_index, Some(input), key)? {
+            self.exit = Some(exit);
+            return Ok(ControlOutcome::Transitioned);
+        }
+
+        let next_state = self
+            .current_state
+            .transition_with_input(display_selection_index, input, key)?;
+        Ok(self.enter_state(next_state, display_selection_index))
+    }
+
+    fn go_back(&mut self) -> Result<Option<usize>, StateTransitionError> {
+        let previous = self.history.pop().ok_or(StateTransitionError::HistoryEmpty)?;
+        let mut previous_state = previous.state;
+        if previous_state.is_refreshed_on_back() {
+            // if the state can't be built again it is shown as it was left
+            match previous_state.refresh() {
+                Some(Ok(refreshed_state)) => previous_state = refreshed_state,
+                Some(Err(e)) => self.background_errors.push(e),
+                None => {}
+            }
+        }
+
+        let line_count = previous_state.get_display().lines.len();
+        self.current_state = previous_state;
+        self.is_auto_transition_pending = false;
+        Ok(previous
+            .display_selection_index
+            .filter(|_| line_count > 0)
+            .map(|idx| idx.min(line_count - 1)))
+    }
+
+    fn refresh(&mut self) -> Result<(), StateTransitionError> {
+        let refreshed_state = self
+            .current_state
+            .refresh()
+            .ok_or(StateTransitionError::NotRefreshable)??;
+        self.replace_current_state(refreshed_state);
+        Ok(())
+    }
+
+    fn is_refreshable(&self) -> bool {
+        self.current_state.is_refreshable()
+    }
+
+    fn get_breadcrumb(&self) -> Vec<String> {
+        self.history
+            .iter()
+            .map(|entry| &entry.state)
+            .chain([&self.current_state])
+            .map(State::get_name)
+            .collect()
+    }
+
     fn get_display(&self) -> &display::Display {
         self.current_state.get_display()
     }
@@ -65,4 +261,514 @@ impl<T: Transit> TerminalFlow for Workflow<T> {
     fn get_state_controls(&self) -> Vec<Control> {
         self.current_state.get_controls()
     }
+
+    fn get_applicable_state_controls(&self, display_selection_index: Option<usize>, input: Option<&str>) -> Vec<Control> {
+        self.current_state.get_applicable_controls(display_selection_index, input)
+    }
+
+    fn get_state_kind(&self) -> StateKind {
+        self.current_state.get_kind()
+    }
+
+    fn get_terminal_handover(
+        &self,
+        display_selection_index: Option<usize>,
+        input: Option<&str>,
+        key: &Key,
+    ) -> Option<TerminalHandover> {
+        self.current_state
+            .get_terminal_handover(display_selection_index, input, key)
+    }
+
+    fn get_marked_terminal_handover(&self, marked_display_indices: &[usize], key: &Key) -> Option<TerminalHandover> {
+        self.current_state
+            .get_marked_terminal_handover(marked_display_indices, key)
+    }
+
+    fn get_confirmation(&self, display_selection_index: Option<usize>, input: Option<&str>, key: &Key) -> Option<String> {
+        self.current_state
+            .get_confirmation(display_selection_index, input, key)
+    }
+
+    fn get_marked_confirmation(&self, marked_display_indices: &[usize], key: &Key) -> Option<String> {
+        self.current_state
+            .get_marked_confirmation(marked_display_indices, key)
+    }
+
+    fn hold_control(&mut self, display_selection_index: Option<usize>, marked_display_indices: &[usize], input: Option<&str>, key: &Key) {
+        self.held_control = Some(self.current_state.hold_control(display_selection_index, marked_display_indices, input, key));
+    }
+
+    fn run_held_control(&mut self) -> Result<ControlOutcome, StateTransitionError> {
+        let held_control = self
+            .held_control
+            .take()
+            .filter(|held_control| self.current_state.is_holding_state(held_control))
+            .ok_or(StateTransitionError::HeldStateLeft)?;
+        if let Some(exit) = self.current_state.exit_held(&held_control)? {
+            self.exit = Some(exit);
+            return Ok(ControlOutcome::Transitioned);
+        }
+
+        let next_state = self.current_state.transition_held(&held_control)?;
+        Ok(self.enter_state(next_state, held_control.get_display_selection_index()))
+    }
+
+    fn get_held_terminal_handover(&self) -> Option<TerminalHandover> {
+        let held_control = self.held_control.as_ref()?;
+        self.current_state.get_held_terminal_handover(held_control)
+    }
+
+    fn get_exit(&self) -> Option<&FlowExit> {
+        self.exit.as_ref()
+    }
+
+    fn run_due_auto_transition(&mut self) -> bool {
+        self.run_auto_transitions()
+    }
+
+    fn run_due_refresh(&mut self) -> bool {
+        if !self.current_state.is_refresh_due() {
+            return false;
+        }
+        match self.current_state.refresh() {
+            Some(Ok(refreshed_state)) => {
+                self.replace_current_state(refreshed_state);
+                true
+            }
+            // a failing refresh keeps showing the last lines and tries again after the next interval
+            refresh_result => {
+                if let Some(Err(e)) = refresh_result {
+                    self.background_errors.push(e);
+                }
+                self.current_state.postpone_refresh();
+                false
+            }
+        }
+    }
+
+    fn is_background_work_due(&self) -> bool {
+        let is_auto_transition_due = self.is_auto_transition_pending
+            && matches!(self.current_state.get_auto_transition_status(), AutoTransitionStatus::Due(_));
+        is_auto_transition_due || self.current_state.is_refresh_due()
+    }
+
+    fn take_background_errors(&mut self) -> Vec<StateTransitionError> {
+        std::mem::take(&mut self.background_errors)
+    }
+
+    fn poll_stream(&mut self) -> Option<StreamUpdate> {
+        self.current_state.poll_stream()
+    }
+
+    fn is_following_stream(&self) -> bool {
+        self.current_state.is_following_stream()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Workflow, MAX_CHAINED_AUTO_TRANSITIONS};
+    use crate::state::{AutoTransition, AutoTrigger, State, StreamSettings, TerminalExit, WorkflowState};
+    use crate::transition::{ExitRoute, Transition};
+    use crate::variable::{Variable, VariableSet};
+    use crate::variable_mapping::{RegexVariableExtractor, VariableInjector};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::time::Duration;
+    use tuiflow_model_contracts::command_runner::{CommandOutput, CommandRunnerError, ExitStatus, MockCommandRunner};
+    use tuiflow_model_contracts::control::{Control, Key};
+    use tuiflow_model_contracts::error::StateTransitionError;
+    use tuiflow_model_contracts::output_stream::{OutputStream, StreamUpdate};
+    use tuiflow_model_contracts::terminal_flow::{ControlOutcome, TerminalFlow};
+
+    type TestTransition = Transition<MockCommandRunner, RegexVariableExtractor>;
+    type TestState = Rc<RefCell<WorkflowState<TestTransition>>>;
+
+    fn state(name: &str) -> TestState {
+        configured_state(name, |state| state)
+    }
+
+    fn configured_state(name: &str, configure: impl FnOnce(WorkflowState<TestTransition>) -> WorkflowState<TestTransition>) -> TestState {
+        Rc::new(RefCell::new(configure(WorkflowState::new(name, VariableInjector::new("<x>".to_string()), vec![]))))
+    }
+
+    fn auto_state(name: &str, trigger: AutoTrigger) -> TestState {
+        configured_state(name, |state| state.with_auto_transition(AutoTransition::new(Key::Enter, trigger)))
+    }
+
+    /// The flow in the state showing a single line, whose variable `x` is the line.
+    fn workflow_on_line(state: &TestState, line: &str) -> Workflow<TestTransition> {
+        let variable_set: VariableSet = vec![Variable::new("x".into(), line.to_string())].into_iter().collect();
+        Workflow::from_state(State::new(Rc::clone(state), vec![variable_set.with_source_line(line.to_string())]), "app".to_string())
+    }
+
+    /// A runner expecting to be run once per output, returning the outputs in order.
+    fn runner_returning(outputs: Vec<Result<CommandOutput, CommandRunnerError>>) -> MockCommandRunner {
+        let mut command_runner = MockCommandRunner::default();
+        let times = outputs.len();
+        let mut outputs = outputs.into_iter();
+        command_runner
+            .expect_run_command()
+            .times(times)
+            .returning(move |_| outputs.next().unwrap());
+        command_runner
+    }
+
+    fn runner_printing(stdouts: &[&str]) -> MockCommandRunner {
+        runner_returning(stdouts.iter().map(|stdout| Ok(CommandOutput::from_stdout(stdout.to_string()))).collect())
+    }
+
+    fn transition(key: Key, to: &TestState, command: &str, command_runner: MockCommandRunner) -> TestTransition {
+        Transition::new(
+            Control::new(command, key),
+            Rc::clone(to),
+            VariableInjector::new(command.to_string()),
+            command_runner,
+            RegexVariableExtractor::new("(?<x>.+)").unwrap(),
+        )
+    }
+
+    fn add_transition(from: &TestState, to: &TestState, output: &'static str, times: usize) {
+        from.borrow_mut()
+            .add_transition(Key::Enter, transition(Key::Enter, to, "cmd", runner_printing(&vec![output; times])));
+    }
+
+    #[test]
+    fn from_state_with_single_line_trigger_skips_state_with_one_line() {
+        let namespaces = auto_state("namespaces", AutoTrigger::SingleLine);
+        let pods = state("pods");
+        add_transition(&namespaces, &pods, "pod-1\npod-2", 1);
+
+        let workflow = workflow_on_line(&namespaces, "default");
+        assert_eq!(workflow.get_state_title(), "pods");
+    }
+
+    #[test]
+    fn from_state_with_cycle_of_auto_states_stops_chaining() {
+        let first = auto_state("first", AutoTrigger::Always);
+        let second = auto_state("second", AutoTrigger::Always);
+        add_transition(&first, &second, "x", MAX_CHAINED_AUTO_TRANSITIONS / 2);
+        add_transition(&second, &first, "x", MAX_CHAINED_AUTO_TRANSITIONS / 2);
+
+        let mut workflow = Workflow::from_state(State::new(Rc::clone(&first), vec![]), "app".to_string());
+        assert_eq!(workflow.get_state_title(), "first");
+        assert!(!workflow.run_due_auto_transition());
+    }
+
+    #[test]
+    fn from_state_with_auto_transition_on_exit_control_ends_flow() {
+        let pick = configured_state("pick", |state| {
+            state
+                .with_auto_transition(AutoTransition::new(Key::Enter, AutoTrigger::SingleLine))
+                .with_terminal_exit(TerminalExit::new(Control::new("pick", Key::Enter), VariableInjector::new("<x>".to_string()), 0))
+        });
+
+        let workflow = workflow_on_line(&pick, "main");
+        assert_eq!(workflow.get_exit().map(|exit| exit.output.as_str()), Some("main"));
+    }
+
+    #[test]
+    fn go_back_restores_previous_state_and_selection_without_running_commands() {
+        let files = state("files");
+        let content = state("content");
+        add_transition(&files, &content, "hello", 1);
+        let lines: Vec<VariableSet> = ["a.txt", "b.txt"]
+            .into_iter()
+            .map(|file| {
+                let variable_set: VariableSet = vec![Variable::new("x".into(), file.to_string())].into_iter().collect();
+                variable_set.with_source_line(file.to_string())
+            })
+            .collect();
+
+        let mut workflow = Workflow::from_state(State::new(files, lines), "app".to_string());
+        workflow.run_control(Some(1), &Key::Enter).unwrap();
+        assert_eq!(workflow.get_breadcrumb(), vec!["files".to_string(), "content".to_string()]);
+
+        assert_eq!(workflow.go_back(), Ok(Some(1)));
+        assert_eq!(workflow.get_state_title(), "files");
+        assert_eq!(workflow.get_display().lines.len(), 2);
+        assert_eq!(workflow.go_back(), Err(StateTransitionError::HistoryEmpty));
+    }
+
+    #[test]
+    fn go_back_into_state_refreshed_on_back_reruns_its_command() {
+        let start = state("start");
+        let files = configured_state("files", |state| state.with_refresh_on_back(true));
+        let content = state("content");
+        add_transition(&start, &files, "a.txt", 2);
+        add_transition(&files, &content, "hello", 1);
+
+        let mut workflow = workflow_on_line(&start, "dir");
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+
+        assert_eq!(workflow.go_back(), Ok(Some(0)));
+        assert_eq!(workflow.get_state_title(), "files");
+    }
+
+    #[test]
+    fn go_back_into_state_refreshed_on_back_reports_failed_refresh_and_keeps_lines() {
+        let start = state("start");
+        let files = configured_state("files", |state| state.with_refresh_on_back(true));
+        let content = state("content");
+        let command_runner = runner_returning(vec![
+            Ok(CommandOutput::from_stdout("a.txt".to_string())),
+            Err(CommandRunnerError::Cancelled { command: "ls".to_string() }),
+        ]);
+        start.borrow_mut().add_transition(Key::Enter, transition(Key::Enter, &files, "ls", command_runner));
+        add_transition(&files, &content, "hello", 1);
+
+        let mut workflow = workflow_on_line(&start, "dir");
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+
+        assert_eq!(workflow.go_back(), Ok(Some(0)));
+        assert_eq!(workflow.get_display().lines, vec!["a.txt".into()]);
+        assert_eq!(
+            workflow.take_background_errors(),
+            vec![StateTransitionError::CommandExecutionError(CommandRunnerError::Cancelled { command: "ls".to_string() })]
+        );
+    }
+
+    #[test]
+    fn poll_stream_appends_lines_within_cap_until_state_is_left() {
+        let start = state("start");
+        let log = configured_state("log", |state| state.with_stream(StreamSettings { max_lines: 2, follow: true }));
+        let (line_sender, line_receiver) = mpsc::channel();
+        let is_stopped = Arc::new(AtomicBool::new(false));
+        let is_stopped_by_stream = Arc::clone(&is_stopped);
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_streaming_command()
+            .return_once(move |_| {
+                Ok(OutputStream::new(line_receiver, move || is_stopped_by_stream.store(true, Ordering::Relaxed)))
+            });
+        start.borrow_mut().add_transition(Key::Enter, transition(Key::Enter, &log, "tail -f <x>", command_runner));
+        add_transition(&log, &start, "x", 1);
+
+        let mut workflow = workflow_on_line(&start, "app.log");
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+        assert_eq!(workflow.poll_stream(), None);
+
+        ["first", "second", "third"].into_iter().for_each(|line| line_sender.send(line.to_string()).unwrap());
+        assert_eq!(workflow.poll_stream(), Some(StreamUpdate { appended: 3, dropped: 1 }));
+        assert_eq!(workflow.get_display().lines, vec!["second".into(), "third".into()]);
+        assert!(workflow.is_following_stream());
+
+        workflow.run_control(Some(1), &Key::Enter).unwrap();
+        assert!(is_stopped.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn run_due_refresh_rebuilds_state_once_its_interval_has_passed() {
+        let start = state("start");
+        let pods = configured_state("pods", |state| state.with_refresh_interval(Duration::ZERO));
+        let command_runner = runner_printing(&["api-1 Running\napi-2 Running", "api-1 Running"]);
+        start.borrow_mut().add_transition(Key::Enter, transition(Key::Enter, &pods, "kubectl get pods", command_runner));
+
+        let mut workflow = workflow_on_line(&start, "ns");
+        assert!(!workflow.run_due_refresh());
+
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+        assert_eq!(workflow.get_display().lines.len(), 2);
+        assert!(workflow.run_due_refresh());
+        assert_eq!(workflow.get_display().lines, vec!["api-1 Running".into()]);
+        assert_eq!(workflow.get_state_title(), "pods");
+    }
+
+    #[test]
+    fn run_control_with_side_effect_stays_in_state_and_rebuilds_it() {
+        let start = state("start");
+        let files = state("files");
+        let list = transition(Key::Enter, &files, "ls <x>", runner_printing(&["a.txt\nb.txt", "b.txt"]));
+        start.borrow_mut().add_transition(Key::Enter, list);
+        let delete = transition(Key::Char('d'), &files, "rm -v <x>", runner_printing(&["removed a.txt\n"])).with_side_effect(true);
+        files.borrow_mut().add_transition(Key::Char('d'), delete);
+
+        let mut workflow = workflow_on_line(&start, "dir");
+        assert_eq!(workflow.run_control(Some(0), &Key::Enter), Ok(ControlOutcome::Transitioned));
+        assert_eq!(
+            workflow.run_control(Some(0), &Key::Char('d')),
+            Ok(ControlOutcome::Refreshed { toast: Some("removed a.txt".to_string()) })
+        );
+        assert_eq!(workflow.get_display().lines, vec!["b.txt".into()]);
+        assert_eq!(workflow.get_breadcrumb(), vec!["start".to_string(), "files".to_string()]);
+    }
+
+    #[test]
+    fn refresh_reruns_command_of_current_state_only_if_it_was_built_by_one() {
+        let start = state("start");
+        let files = state("files");
+        add_transition(&start, &files, "a.txt", 2);
+
+        let mut workflow = workflow_on_line(&start, "dir");
+        assert!(!workflow.is_refreshable());
+        assert_eq!(workflow.refresh(), Err(StateTransitionError::NotRefreshable));
+
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+        assert!(workflow.is_refreshable());
+        assert_eq!(workflow.refresh(), Ok(()));
+        assert_eq!(workflow.get_display().lines, vec!["a.txt".into()]);
+        assert_eq!(workflow.get_breadcrumb(), vec!["start".to_string(), "files".to_string()]);
+    }
+
+    /// Enters the state through `rm -rf dir` after its confirmation, failing the test if the command runs again.
+    fn enter_through_confirmed_command(result: &TestState) -> Workflow<TestTransition> {
+        let start = state("start");
+        let delete = transition(Key::Enter, result, "rm -rf <x>", runner_printing(&["deleted"]))
+            .with_confirmation(VariableInjector::new("Delete <x>?".to_string()));
+        start.borrow_mut().add_transition(Key::Enter, delete);
+
+        let mut workflow = workflow_on_line(&start, "dir");
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+        workflow
+    }
+
+    #[test]
+    fn refresh_refuses_to_rerun_command_that_asks_for_confirmation() {
+        let result = state("result");
+        let mut workflow = enter_through_confirmed_command(&result);
+
+        assert!(!workflow.is_refreshable());
+        assert_eq!(workflow.refresh(), Err(StateTransitionError::NotRefreshable));
+        assert_eq!(workflow.get_display().lines, vec!["deleted".into()]);
+    }
+
+    #[test]
+    fn run_due_refresh_never_reruns_command_that_asks_for_confirmation() {
+        let result = configured_state("result", |state| state.with_refresh_interval(Duration::ZERO));
+        let mut workflow = enter_through_confirmed_command(&result);
+
+        assert!(!workflow.is_background_work_due());
+        assert!(!workflow.run_due_refresh());
+        assert_eq!(workflow.get_display().lines, vec!["deleted".into()]);
+    }
+
+    #[test]
+    fn go_back_into_state_refreshed_on_back_keeps_lines_of_command_that_asks_for_confirmation() {
+        let result = configured_state("result", |state| state.with_refresh_on_back(true));
+        let details = state("details");
+        add_transition(&result, &details, "gone", 1);
+        let mut workflow = enter_through_confirmed_command(&result);
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+
+        assert_eq!(workflow.go_back(), Ok(Some(0)));
+        assert_eq!(workflow.get_display().lines, vec!["deleted".into()]);
+        assert!(workflow.take_background_errors().is_empty());
+    }
+
+    #[test]
+    fn run_control_with_side_effect_keeps_lines_of_command_that_asks_for_confirmation() {
+        let result = state("result");
+        let touch = transition(Key::Char('t'), &result, "touch <x>", runner_printing(&[""])).with_side_effect(false);
+        result.borrow_mut().add_transition(Key::Char('t'), touch);
+        let mut workflow = enter_through_confirmed_command(&result);
+
+        assert_eq!(workflow.run_control(Some(0), &Key::Char('t')), Ok(ControlOutcome::Refreshed { toast: None }));
+        assert_eq!(workflow.get_display().lines, vec!["deleted".into()]);
+    }
+
+    #[test]
+    fn run_due_refresh_keeps_delay_of_auto_transition_running() {
+        let start = state("start");
+        let pods = configured_state("pods", |state| {
+            state
+                .with_refresh_interval(Duration::ZERO)
+                .with_auto_transition(AutoTransition::new(Key::Enter, AutoTrigger::Delay(Duration::from_millis(100))))
+        });
+        let done = state("done");
+        add_transition(&start, &pods, "api-1", 2);
+        add_transition(&pods, &done, "ok", 1);
+
+        let mut workflow = workflow_on_line(&start, "ns");
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(workflow.run_due_refresh());
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(workflow.run_due_auto_transition());
+        assert_eq!(workflow.get_state_title(), "done");
+    }
+
+    #[test]
+    fn run_held_control_runs_for_held_line_after_lines_changed() {
+        let start = state("start");
+        let files = configured_state("files", |state| state.with_refresh_interval(Duration::ZERO));
+        let deleted = state("deleted");
+        let list = transition(Key::Enter, &files, "ls", runner_printing(&["a.txt\nb.txt", "c.txt\na.txt"]));
+        start.borrow_mut().add_transition(Key::Enter, list);
+        let mut command_runner = MockCommandRunner::default();
+        command_runner
+            .expect_run_command()
+            .withf(|cmd| cmd.command == "rm b.txt")
+            .once()
+            .returning(|_| Ok(CommandOutput::from_stdout("b.txt".to_string())));
+        files.borrow_mut().add_transition(Key::Char('d'), transition(Key::Char('d'), &deleted, "rm <x>", command_runner));
+
+        let mut workflow = workflow_on_line(&start, "dir");
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+        workflow.hold_control(Some(1), &[], None, &Key::Char('d'));
+        assert!(workflow.run_due_refresh());
+        assert_eq!(workflow.get_display().lines, vec!["c.txt".into(), "a.txt".into()]);
+
+        assert_eq!(workflow.run_held_control(), Ok(ControlOutcome::Transitioned));
+        assert_eq!(workflow.get_state_title(), "deleted");
+    }
+
+    #[test]
+    fn run_held_control_after_state_was_left_fails_without_running_it() {
+        let files = state("files");
+        let content = state("content");
+        add_transition(&files, &content, "hello", 1);
+        let mut workflow = workflow_on_line(&files, "a.txt");
+
+        workflow.hold_control(Some(0), &[], None, &Key::Enter);
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+        assert_eq!(workflow.run_held_control(), Err(StateTransitionError::HeldStateLeft));
+        assert_eq!(workflow.get_state_title(), "content");
+    }
+
+    #[test]
+    fn refresh_of_routed_state_that_now_leads_elsewhere_keeps_state() {
+        let start = state("start");
+        let pods = state("pods");
+        let failed = state("failed");
+        let command_runner = runner_returning(vec![
+            Ok(CommandOutput { exit_status: ExitStatus::Code(1), ..CommandOutput::from_stdout("no cluster".to_string()) }),
+            Ok(CommandOutput::from_stdout("api-1".to_string())),
+        ]);
+        let list = transition(Key::Enter, &pods, "kubectl get pods", command_runner).with_exit_route(ExitRoute::Failure, failed);
+        start.borrow_mut().add_transition(Key::Enter, list);
+
+        let mut workflow = workflow_on_line(&start, "ns");
+        workflow.run_control(Some(0), &Key::Enter).unwrap();
+        assert_eq!(workflow.get_state_title(), "failed");
+
+        assert_eq!(workflow.refresh(), Err(StateTransitionError::RefreshRouted("pods".to_string())));
+        assert_eq!(workflow.get_state_title(), "failed");
+        assert_eq!(workflow.get_display().lines, vec!["no cluster".into()]);
+    }
+
+    #[test]
+    fn failing_auto_transition_keeps_state_and_reports_background_error() {
+        let start = auto_state("start", AutoTrigger::Always);
+        let next = state("next");
+        let command_runner =
+            runner_returning(vec![Err(CommandRunnerError::Spawn { command: "false".to_string(), reason: "no shell".to_string() })]);
+        start.borrow_mut().add_transition(Key::Enter, transition(Key::Enter, &next, "false", command_runner));
+
+        let line = VariableSet::empty().with_source_line("a".to_string());
+        let mut workflow = Workflow::from_state(State::new(start, vec![line]), "app".to_string());
+        assert_eq!(workflow.get_state_title(), "start");
+        assert_eq!(
+            workflow.take_background_errors(),
+            vec![StateTransitionError::CommandExecutionError(CommandRunnerError::Spawn {
+                command: "false".to_string(),
+                reason: "no shell".to_string(),
+            })]
+        );
+        assert!(workflow.take_background_errors().is_empty());
+    }
 }
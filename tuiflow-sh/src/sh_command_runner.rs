@@ -1,28 +1,270 @@
-use tuiflow_model_contracts::command_runner::{CommandRunner, CommandRunnerError};
-use std::process::Command;
+use tuiflow_model_contracts::command_activity::CommandActivity;
+use tuiflow_model_contracts::command_runner::{CommandOutput, CommandRunner, CommandRunnerError, ExitStatus};
+use tuiflow_model_contracts::output_stream::OutputStream;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, LazyLock};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use crate::sh_command::ShCommand;
 
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(20);
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(2); // the time a command gets to end after SIGTERM before SIGKILL
+
+// runners are created without context, so they all report to the activity the ui gets from `get_command_activity`
+static COMMAND_ACTIVITY: LazyLock<CommandActivity> = LazyLock::new(CommandActivity::default);
+
 #[derive(Clone)]
-pub struct ShCommandRunner;
+pub struct ShCommandRunner {
+    timeout: Option<Duration>, // commands running longer are killed
+    command_activity: CommandActivity,
+}
+
+impl ShCommandRunner {
+    /// The commands all runners are waiting for, with the handle to cancel them.
+    pub fn get_command_activity() -> CommandActivity {
+        COMMAND_ACTIVITY.clone()
+    }
+}
 
 impl CommandRunner for ShCommandRunner {
     type Command = ShCommand;
 
-    fn run_command(&self, command: &<Self as CommandRunner>::Command) -> Result<String, CommandRunnerError> {
+    /// Runs the command in its own session, so cancelling it also kills the processes it started.
+    fn run_command(&self, command: &<Self as CommandRunner>::Command) -> Result<CommandOutput, CommandRunnerError> {
         let command_str: &str = command;
-        let cli_result = Command::new("sh").arg("-c").arg(command_str).output();
-        if let Ok(cli_output) = cli_result {
-            if let Ok(cli_result) = String::from_utf8(cli_output.stdout) {
-                return Ok(cli_result)
+        let full_command = Self::get_full_command(command);
+        let spawn_error = |reason: &str| CommandRunnerError::Spawn {
+            command: full_command.clone(),
+            reason: reason.to_string(),
+        };
+        let started_at = Instant::now();
+        let mut child = new_session_command(command_str)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| spawn_error(&e.to_string()))?;
+        let _running_command = self.command_activity.start(command_str);
+
+        // read while waiting, a full pipe would block the command forever
+        let stdout_reader = read_to_end(child.stdout.take().ok_or_else(|| spawn_error("stdout is not piped"))?);
+        let stderr_reader = read_to_end(child.stderr.take().ok_or_else(|| spawn_error("stderr is not piped"))?);
+        let exit_status = loop {
+            if self.command_activity.is_cancel_requested() {
+                kill_process_group(&mut child);
+                return Err(CommandRunnerError::Cancelled { command: full_command });
             }
+            if let Some(timeout) = self.timeout.filter(|timeout| started_at.elapsed() >= *timeout) {
+                kill_process_group(&mut child);
+                return Err(CommandRunnerError::Timeout { command: full_command, timeout });
+            }
+            match child.try_wait() {
+                Ok(Some(exit_status)) => break exit_status,
+                Ok(None) => thread::sleep(CANCEL_POLL_INTERVAL),
+                Err(e) => return Err(spawn_error(&e.to_string())),
+            }
+        };
+
+        let join = |reader: JoinHandle<std::io::Result<Vec<u8>>>| reader.join().ok().and_then(Result::ok).unwrap_or_default();
+        let stdout = join(stdout_reader);
+        let mut output = CommandOutput {
+            stdout: String::new(),
+            stderr: String::from_utf8_lossy(&join(stderr_reader)).into_owned(),
+            exit_status: match (exit_status.code(), exit_status.signal()) {
+                (Some(code), _) => ExitStatus::Code(code),
+                (None, Some(signal)) => ExitStatus::Signal(signal),
+                (None, None) => ExitStatus::Code(-1),
+            },
+            duration: started_at.elapsed(),
+        };
+        match String::from_utf8(stdout) {
+            Ok(stdout) => {
+                output.stdout = stdout;
+                Ok(output)
+            }
+            Err(e) => {
+                output.stdout = String::from_utf8_lossy(e.as_bytes()).into_owned();
+                Err(CommandRunnerError::NonUtf8Output { command: full_command, output })
+            }
+        }
+    }
+
+    fn run_interactive_command(&self, command: &<Self as CommandRunner>::Command) -> Result<(), CommandRunnerError> {
+        let command_str: &str = command;
+        let mut sh_command = Command::new("sh");
+        sh_command.arg("-c").arg(command_str);
+        // stdin/stdout may be piped into tuiflow, the interactive program needs the terminal itself
+        if let Ok(tty) = File::options().read(true).write(true).open("/dev/tty")
+            && let (Ok(tty_out), Ok(tty_err)) = (tty.try_clone(), tty.try_clone())
+        {
+            sh_command.stdin(tty).stdout(tty_out).stderr(tty_err);
         }
 
-        Err(CommandRunnerError {
-            command: format!("{} {}", "sh -c", command_str),
-        })
+        match sh_command.status() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(CommandRunnerError::Spawn {
+                command: Self::get_full_command(command),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    fn run_streaming_command(&self, command: &<Self as CommandRunner>::Command) -> Result<OutputStream, CommandRunnerError> {
+        let command_str: &str = command;
+        let spawn_error = |reason: String| CommandRunnerError::Spawn {
+            command: Self::get_full_command(command),
+            reason,
+        };
+        let mut child = new_session_command(command_str)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| spawn_error(e.to_string()))?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| spawn_error("stdout is not piped".to_string()))?;
+        let (line_sender, line_receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if line_sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        // leaving the state must not wait for the grace period of the kill
+        Ok(OutputStream::new(line_receiver, move || {
+            thread::spawn(move || kill_process_group(&mut child));
+        }))
     }
 
     fn new() -> Self {
-        ShCommandRunner
+        ShCommandRunner {
+            timeout: None,
+            command_activity: Self::get_command_activity(),
+        }
+    }
+
+    fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn get_full_command(command: &<Self as CommandRunner>::Command) -> String {
+        format!("{} {}", "sh -c", &**command)
+    }
+}
+
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> JoinHandle<std::io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut output = vec![];
+        pipe.read_to_end(&mut output).map(|_| output)
+    })
+}
+
+/// The command in a session of its own. Its processes form a group that can be killed together, and without
+/// a controlling terminal they can't be stopped by reading from the terminal tuiflow draws on.
+fn new_session_command(command_str: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_str);
+    // SAFETY: setsid is async-signal-safe, nothing else runs between fork and exec
+    unsafe {
+        command.pre_exec(|| match libc::setsid() {
+            -1 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        });
+    }
+    command
+}
+
+/// Asks the processes of the command to end and kills them if they are still running after a grace period.
+fn kill_process_group(child: &mut Child) {
+    let Ok(process_group_id) = i32::try_from(child.id()) else {
+        _ = child.kill();
+        _ = child.wait();
+        return;
+    };
+    // SAFETY: kill only sends a signal, the negative id addresses the group the child leads
+    let signal_group = |signal| unsafe {
+        libc::kill(-process_group_id, signal);
+    };
+    signal_group(libc::SIGTERM);
+    let terminated_at = Instant::now();
+    while terminated_at.elapsed() < KILL_GRACE_PERIOD {
+        match child.try_wait() {
+            Ok(None) => thread::sleep(CANCEL_POLL_INTERVAL),
+            _ => return,
+        }
+    }
+    signal_group(libc::SIGKILL);
+    _ = child.wait();
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ShCommandRunner, KILL_GRACE_PERIOD};
+    use crate::sh_command::ShCommand;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tuiflow_model_contracts::command_activity::CommandActivity;
+    use tuiflow_model_contracts::command_runner::{CommandRunner, CommandRunnerError};
+
+    // an activity of its own, so the test doesn't cancel the commands of other tests
+    fn new_runner() -> ShCommandRunner {
+        ShCommandRunner {
+            timeout: None,
+            command_activity: CommandActivity::default(),
+        }
+    }
+
+    #[test]
+    fn run_command_is_cancelled_through_command_activity() {
+        let runner = new_runner();
+        let command_activity = runner.command_activity.clone();
+        let canceller = thread::spawn(move || {
+            while command_activity.get_running_commands().is_empty() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            command_activity.request_cancel();
+        });
+
+        let started_at = Instant::now();
+        let result = runner.run_command(&ShCommand::from("sleep 5".to_string()));
+        canceller.join().unwrap();
+        assert!(matches!(result, Err(CommandRunnerError::Cancelled { command }) if command == "sh -c sleep 5"));
+        assert!(started_at.elapsed() < Duration::from_secs(2));
+        assert!(runner.command_activity.get_running_commands().is_empty());
+    }
+
+    #[test]
+    fn run_command_kills_command_ignoring_sigterm_after_grace_period() {
+        let runner = new_runner().with_timeout(Duration::from_millis(100));
+
+        let started_at = Instant::now();
+        let result = runner.run_command(&ShCommand::from("trap '' TERM; sleep 10".to_string()));
+        assert!(matches!(result, Err(CommandRunnerError::Timeout { .. })));
+        assert!(started_at.elapsed() >= KILL_GRACE_PERIOD);
+        assert!(started_at.elapsed() < Duration::from_secs(8));
+    }
+
+    #[test]
+    fn dropping_output_stream_stops_command_without_waiting_for_it() {
+        let runner = new_runner();
+        let command = "trap '' TERM; for i in $(seq 60); do echo line; sleep 0.05; done".to_string();
+        let mut output_stream = runner.run_streaming_command(&ShCommand::from(command)).unwrap();
+        while output_stream.take_new_lines().is_empty() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let stopped_at = Instant::now();
+        drop(output_stream);
+        assert!(stopped_at.elapsed() < Duration::from_millis(500));
     }
 }
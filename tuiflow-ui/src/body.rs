@@ -1,40 +1,139 @@
+mod fuzzy_filter;
+mod line_diff;
+mod line_marks;
+mod markdown;
+
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::{Modifier, Style},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{
-        Block, BorderType, Borders, List, ListItem, ListState, StatefulWidgetRef, WidgetRef,
+        Block, BorderType, Borders, List, ListItem, ListState, Paragraph, StatefulWidgetRef, WidgetRef, Wrap,
     },
 };
 use tuiflow_model_contracts::control::Key;
 use tuiflow_model_contracts::display::Display;
+use tuiflow_model_contracts::output_stream::StreamUpdate;
+use tuiflow_model_contracts::state_kind::StateKind;
 use tuiflow_model_contracts::terminal_flow::TerminalFlow;
+use crate::input_widget::{InputState, InputViewModel};
 use crate::io::InputUpdatedViewModel;
+use crate::reserved_controls::ReservedControls;
+use line_diff::LineChanges;
+use line_marks::LineMarks;
 
 // TODO: find a better solution than RefCell for everything mutable
 pub struct BodyWidget<'a> {
     main_block: Block<'static>,
-    list: List<'a>,
+    content: BodyContent<'a>,
+}
+
+enum BodyContent<'a> {
+    List(Vec<(usize, ListItem<'a>)>), // the items with the index of their line in the display
+    Prose(Box<Paragraph<'a>>),
 }
 
 impl<'a> BodyWidget<'a> {
     pub fn new(view_model: &'a BodyViewModel) -> Self {
-        let list_items = view_model
-            .get_list_items()
-            .into_iter()
-            .map(|item: &str| ListItem::<'a>::new(ratatui::text::Line::<'a>::raw(item)))
-            .collect::<Vec<_>>();
+        let content = match view_model.kind {
+            StateKind::Prose { markdown } => BodyContent::Prose(Box::new(Self::build_prose(view_model, markdown))),
+            _ => BodyContent::List(Self::build_list(view_model)),
+        };
 
         Self {
             main_block: Block::new()
                 .style(Style::default())
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded),
-            list: List::new(list_items).highlight_style(
-                Style::new()
-                    .add_modifier(Modifier::BOLD)
-                    .bg(ratatui::style::Color::Cyan),
-            ),
+            content,
+        }
+    }
+
+    fn build_list(view_model: &'a BodyViewModel) -> Vec<(usize, ListItem<'a>)> {
+        view_model
+            .filtered_lines
+            .iter()
+            .zip(view_model.get_list_items())
+            .map(|(filtered_line, (item, matched_char_indices))| {
+                let list_item = ListItem::<'a>::new(Self::highlight_matches(item, matched_char_indices));
+                (filtered_line.line_index, list_item)
+            })
+            .collect()
+    }
+
+    /// The list with the marked and recently changed lines highlighted, as both live in the state.
+    fn mark_list(list_items: &[(usize, ListItem<'a>)], state: &BodyState) -> List<'a> {
+        let marked_style = Style::new().add_modifier(Modifier::BOLD).fg(Color::Magenta);
+        let changed_style = Style::new().fg(Color::Green);
+        let is_changed = |line_index: usize| {
+            state
+                .line_changes
+                .as_ref()
+                .is_some_and(|line_changes| line_changes.is_changed(line_index))
+        };
+        let list_items = list_items
+            .iter()
+            .map(|(line_index, list_item)| {
+                if state.marks.is_marked(*line_index) {
+                    list_item.clone().style(marked_style)
+                } else if is_changed(*line_index) {
+                    list_item.clone().style(changed_style)
+                } else {
+                    list_item.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        List::new(list_items).highlight_style(
+            Style::new()
+                .add_modifier(Modifier::BOLD)
+                .bg(ratatui::style::Color::Cyan),
+        )
+    }
+
+    fn build_prose(view_model: &'a BodyViewModel, markdown: bool) -> Paragraph<'a> {
+        let lines = if markdown {
+            markdown::render_markdown(&view_model.display.lines)
+        } else {
+            view_model
+                .display
+                .lines
+                .iter()
+                .map(|line| Line::raw(line.0.as_str()))
+                .collect()
+        };
+        Paragraph::new(lines).wrap(Wrap { trim: false })
+    }
+
+    fn highlight_matches(item: &'a str, matched_char_indices: &[usize]) -> Line<'a> {
+        if matched_char_indices.is_empty() {
+            return Line::raw(item);
+        }
+
+        let match_style = Style::new()
+            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+            .fg(Color::Yellow);
+        let mut spans = vec![];
+        let mut span_start = 0;
+        let mut span_is_match = false;
+        for (char_idx, (byte_idx, _)) in item.char_indices().enumerate() {
+            let char_is_match = matched_char_indices.contains(&char_idx);
+            if char_is_match != span_is_match {
+                spans.push(Self::span(&item[span_start..byte_idx], span_is_match, match_style));
+                span_start = byte_idx;
+                span_is_match = char_is_match;
+            }
+        }
+        spans.push(Self::span(&item[span_start..], span_is_match, match_style));
+        Line::from(spans)
+    }
+
+    fn span(content: &'a str, is_match: bool, match_style: Style) -> Span<'a> {
+        if is_match {
+            Span::styled(content, match_style)
+        } else {
+            Span::raw(content)
         }
     }
 }
@@ -42,70 +141,318 @@ impl<'a> BodyWidget<'a> {
 impl<'a> StatefulWidgetRef for BodyWidget<'a> {
     type State = BodyState;
     fn render_ref(&self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-        self.main_block.render_ref(area, buf);
-        let block_content_area = self.main_block.inner(area);
-        StatefulWidgetRef::render_ref(&self.list, block_content_area, buf, &mut state.list_state)
+        let mut main_block = match state.get_filter_title() {
+            Some(filter_title) => self.main_block.clone().title_bottom(filter_title),
+            None => self.main_block.clone(),
+        };
+        if !state.marks.is_empty() {
+            main_block = main_block.title_top(Line::from(format!(" {} marked ", state.marks.len())).right_aligned());
+        }
+        let removed_line_count = state
+            .line_changes
+            .as_ref()
+            .map_or(0, LineChanges::get_removed_line_count);
+        if removed_line_count > 0 {
+            main_block = main_block.title_top(
+                Line::styled(format!(" {removed_line_count} removed "), Style::new().fg(Color::Red)).right_aligned(),
+            );
+        }
+        main_block.render_ref(area, buf);
+        let block_content_area = main_block.inner(area);
+        match &self.content {
+            BodyContent::List(list_items) => {
+                let list = Self::mark_list(list_items, state);
+                StatefulWidgetRef::render_ref(&list, block_content_area, buf, &mut state.list_state)
+            }
+            BodyContent::Prose(paragraph) => {
+                let max_scroll = paragraph
+                    .line_count(block_content_area.width)
+                    .saturating_sub(block_content_area.height as usize);
+                state.prose_scroll = state.prose_scroll.min(max_scroll as u16);
+                state.prose_max_scroll = max_scroll as u16;
+                paragraph
+                    .clone()
+                    .scroll((state.prose_scroll, 0))
+                    .render_ref(block_content_area, buf)
+            }
+        }
     }
 }
 
+struct FilteredLine {
+    line_index: usize,
+    matched_char_indices: Vec<usize>,
+}
+
 pub struct BodyViewModel {
     display: Display,
+    kind: StateKind,
+    filtered_lines: Vec<FilteredLine>,
     selection_up: Key,
     selection_down: Key,
+    filter: Key,
+    toggle_mark: Key,
+    mark_all: Key,
+    invert_marks: Key,
 }
 
 impl BodyViewModel {
-    pub fn new(display: Display, selection_up: Key, selection_down: Key) -> Self {
+    pub fn new(display: Display, kind: StateKind, reserved_controls: &ReservedControls) -> Self {
         //TODO: at some point use references for display
+        let filtered_lines = Self::filter_lines(&display, "");
         Self {
             display,
-            selection_down,
-            selection_up,
+            kind,
+            filtered_lines,
+            selection_down: reserved_controls.selection_down.get_key(),
+            selection_up: reserved_controls.selection_up.get_key(),
+            filter: reserved_controls.filter.get_key(),
+            toggle_mark: reserved_controls.toggle_mark.get_key(),
+            mark_all: reserved_controls.mark_all.get_key(),
+            invert_marks: reserved_controls.invert_marks.get_key(),
         }
     }
 
-    pub fn get_list_items<'a>(&'a self) -> Vec<&'a str> {
-        self.display
+    pub fn get_list_items<'a>(&'a self) -> Vec<(&'a str, &'a [usize])> {
+        self.filtered_lines
+            .iter()
+            .map(|filtered_line| {
+                (
+                    self.display.lines[filtered_line.line_index].0.as_str(),
+                    filtered_line.matched_char_indices.as_slice(),
+                )
+            })
+            .collect::<Vec<(&'a str, &'a [usize])>>()
+    }
+
+    /// Maps the selection in the (possibly filtered) list back to the index of the line in the display.
+    pub fn get_selected_line_index(&self, state: &BodyState) -> Option<usize> {
+        state
+            .get_selected_list_index()
+            .and_then(|idx| self.filtered_lines.get(idx))
+            .map(|filtered_line| filtered_line.line_index)
+    }
+
+    /// Selects the line of the display in the (possibly filtered) list, the first one if it is filtered out.
+    pub fn select_line(&self, state: &mut BodyState, line_index: Option<usize>) {
+        let list_index = self
+            .filtered_lines
+            .iter()
+            .position(|filtered_line| Some(filtered_line.line_index) == line_index);
+        match list_index {
+            Some(list_index) => state.list_state.select(Some(list_index)),
+            None => state.select_first(),
+        }
+    }
+
+    /// The marked lines by their index in the display, empty if the state can't mark lines.
+    pub fn get_marked_line_indices(&self, state: &BodyState) -> Vec<usize> {
+        state.marks.get_line_indices()
+    }
+
+    /// Whether lines can be marked, prose and input states run their controls for a single line.
+    fn can_mark(&self) -> bool {
+        self.kind == StateKind::Lines
+    }
+
+    fn filter_lines(display: &Display, filter: &str) -> Vec<FilteredLine> {
+        display
             .lines
             .iter()
-            .map(|line| line.0.as_str())
-            .collect::<Vec<&'a str>>()
+            .enumerate()
+            .filter_map(|(line_index, line)| {
+                fuzzy_filter::fuzzy_match(filter, &line.0).map(|matched_char_indices| FilteredLine {
+                    line_index,
+                    matched_char_indices,
+                })
+            })
+            .collect()
+    }
+
+    /// Whether the key is consumed by the open filter prompt.
+    pub fn is_filter_prompt_key(&self, state: &BodyState, key: &Key) -> bool {
+        state.is_filter_prompt_open()
+            && (InputViewModel::is_text_editing_key(key) || *key == Key::Enter || *key == Key::Esc)
+    }
+
+    /// Shows the display of the current state, resetting selection, filter and scroll if it changed.
+    pub fn refresh(&mut self, state: &mut BodyState, workflow: &impl TerminalFlow) {
+        let kind = workflow.get_state_kind();
+        if *workflow.get_display() != self.display || kind != self.kind {
+            state.select_first();
+            state.filter_input.clear();
+            state.marks.clear();
+            state.line_changes = None;
+            state.prose_scroll = 0;
+            self.display = workflow.get_display().clone();
+            self.kind = kind;
+            self.filtered_lines = Self::filter_lines(&self.display, "");
+        }
+    }
+
+    /// Shows the lines a streaming state received, keeping selection, filter and marks on their lines.
+    /// While following, the newest line stays in view as long as the user has not moved away from it.
+    pub fn append_lines(&mut self, state: &mut BodyState, workflow: &impl TerminalFlow, stream_update: StreamUpdate) {
+        let is_at_end = match self.kind {
+            StateKind::Prose { .. } => state.prose_scroll >= state.prose_max_scroll,
+            _ => state
+                .get_selected_list_index()
+                .is_none_or(|idx| idx + 1 >= self.filtered_lines.len()),
+        };
+        let selected_line_index = self
+            .get_selected_line_index(state)
+            .and_then(|idx| idx.checked_sub(stream_update.dropped));
+
+        self.display = workflow.get_display().clone();
+        self.filtered_lines = Self::filter_lines(&self.display, state.filter_input.get_text());
+        state.drop_first_lines(stream_update.dropped);
+        if workflow.is_following_stream() && is_at_end {
+            state.prose_scroll = u16::MAX;
+            state.list_state.select(self.filtered_lines.len().checked_sub(1));
+        } else {
+            self.select_line(state, selected_line_index);
+        }
+    }
+
+    /// Shows the lines of the refreshed state, keeping selection, filter and marks on the same items
+    /// and highlighting what changed.
+    pub fn refresh_lines(&mut self, state: &mut BodyState, workflow: &impl TerminalFlow) {
+        let new_display = workflow.get_display().clone();
+        let item_lines = line_diff::ItemLines::new(&new_display);
+        let find_same_item = |line_index: usize| item_lines.find_same_item(&self.display, line_index);
+        let previous_selected_line_index = self.get_selected_line_index(state);
+        let selected_line_index = previous_selected_line_index.and_then(find_same_item).or_else(|| {
+            // the selection of a removed line moves on to the line that took its place
+            let last_line_index = new_display.lines.len().checked_sub(1)?;
+            previous_selected_line_index.map(|line_index| line_index.min(last_line_index))
+        });
+        let marked_line_indices = state
+            .marks
+            .get_line_indices()
+            .into_iter()
+            .filter_map(find_same_item)
+            .collect::<Vec<_>>();
+
+        state.marks.clear();
+        state.marks.mark_all(marked_line_indices);
+        // a refresh that changed nothing keeps the highlight of the one before
+        let line_changes = LineChanges::new(&self.display, &new_display);
+        if !line_changes.is_empty() {
+            state.line_changes = Some(line_changes);
+        }
+        self.display = new_display;
+        self.kind = workflow.get_state_kind();
+        self.filtered_lines = Self::filter_lines(&self.display, state.filter_input.get_text());
+        if !matches!(self.kind, StateKind::Prose { .. }) {
+            self.select_line(state, selected_line_index);
+        }
+    }
+
+    fn update_filter_prompt(&mut self, state: &mut BodyState, key: &Key) {
+        match key {
+            Key::Enter => state.is_filter_prompt_open = false,
+            Key::Esc => {
+                state.is_filter_prompt_open = false;
+                state.filter_input.clear();
+            }
+            _ => state.filter_input.edit(key),
+        }
+        self.filtered_lines = Self::filter_lines(&self.display, state.filter_input.get_text());
+        state.select_first();
     }
 }
 
 impl InputUpdatedViewModel for BodyViewModel {
     type ViewState = BodyState;
-    fn needs_update(&self, _: &Self::ViewState, _: &impl TerminalFlow, key: &Key) -> bool {
-        *key == self.selection_down || *key == self.selection_up
+    fn needs_update(&self, state: &Self::ViewState, _: &impl TerminalFlow, key: &Key) -> bool {
+        *key == self.selection_down
+            || *key == self.selection_up
+            || *key == self.filter
+            || (self.can_mark() && [self.toggle_mark, self.mark_all, self.invert_marks].contains(key))
+            || self.is_filter_prompt_key(state, key)
     }
 
     fn update(&mut self, state: &mut Self::ViewState, workflow: &mut impl TerminalFlow, key: &Key) {
-        if *key == self.selection_down {
-            state.move_selection_down();
-        } else if *key == self.selection_up {
-            state.move_selection_up();
+        if self.is_filter_prompt_key(state, key) {
+            self.update_filter_prompt(state, key);
+            return;
         }
 
-        if *workflow.get_display() != self.display {
-            state.select_first();
+        let is_prose = matches!(self.kind, StateKind::Prose { .. });
+        if *key == self.selection_down {
+            if is_prose {
+                state.prose_scroll = state.prose_scroll.saturating_add(1);
+            } else {
+                state.move_selection_down();
+            }
+        } else if *key == self.selection_up {
+            if is_prose {
+                state.prose_scroll = state.prose_scroll.saturating_sub(1);
+            } else {
+                state.move_selection_up();
+            }
+        } else if *key == self.filter && !is_prose {
+            state.is_filter_prompt_open = true;
+        } else if self.can_mark() {
+            let visible_line_indices = self.filtered_lines.iter().map(|filtered_line| filtered_line.line_index);
+            if *key == self.toggle_mark {
+                if let Some(line_index) = self.get_selected_line_index(state) {
+                    state.marks.toggle(line_index);
+                    state.move_selection_down();
+                }
+            } else if *key == self.mark_all {
+                state.marks.mark_all(visible_line_indices);
+            } else if *key == self.invert_marks {
+                state.marks.invert(visible_line_indices);
+            }
         }
 
-        self.display = workflow.get_display().clone()
+        self.refresh(state, workflow);
+        state.clamp_selection(self.filtered_lines.len());
     }
 }
 
 pub struct BodyState {
     list_state: ListState,
+    filter_input: InputState,
+    is_filter_prompt_open: bool,
+    prose_scroll: u16,
+    prose_max_scroll: u16, // the scroll that shows the last line, as of the last render
+    marks: LineMarks,
+    line_changes: Option<LineChanges>, // what the last refresh of the state changed
+}
+
+impl Default for BodyState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BodyState {
     pub fn new() -> Self {
         let mut list_state = ListState::default();
         list_state.select_first();
-        Self { list_state }
+        Self {
+            list_state,
+            filter_input: InputState::new(),
+            is_filter_prompt_open: false,
+            prose_scroll: 0,
+            prose_max_scroll: 0,
+            marks: LineMarks::default(),
+            line_changes: None,
+        }
     }
+    /// Keeps marks and scroll on their lines when the first lines of the display were dropped.
+    /// Prose scrolls back one row per dropped line, so wrapped lines may still shift the view a little.
+    fn drop_first_lines(&mut self, dropped_line_count: usize) {
+        self.marks.shift(dropped_line_count);
+        let dropped_row_count = u16::try_from(dropped_line_count).unwrap_or(u16::MAX);
+        self.prose_scroll = self.prose_scroll.saturating_sub(dropped_row_count);
+        self.prose_max_scroll = self.prose_max_scroll.saturating_sub(dropped_row_count);
+    }
+
     pub fn move_selection_down(&mut self) {
-        let next_selection = self.list_state.selected().and_then(|i| Some(i + 1));
+        let next_selection = self.list_state.selected().map(|i| i + 1);
         self.list_state.select(next_selection);
     }
 
@@ -113,15 +460,60 @@ impl BodyState {
         let next_selection =
             self.list_state
                 .selected()
-                .and_then(|i| if i > 0 { Some(i - 1) } else { Some(i) });
+                .map(|i| if i > 0 { i - 1 } else { i });
         self.list_state.select(next_selection);
     }
 
+    /// Keeps the selection on the list, e.g. after moving down from its last line or once the filter shrank it.
+    fn clamp_selection(&mut self, list_length: usize) {
+        let clamped_selection = self
+            .list_state
+            .selected()
+            .map(|idx| idx.min(list_length.saturating_sub(1)));
+        self.list_state.select(clamped_selection);
+    }
+
     pub fn select_first(&mut self) {
         self.list_state.select(Some(0));
     }
 
-    pub fn get_selected_line_index(&self) -> Option<usize> {
+    pub fn get_selected_list_index(&self) -> Option<usize> {
         self.list_state.selected()
     }
+
+    pub fn is_filter_prompt_open(&self) -> bool {
+        self.is_filter_prompt_open
+    }
+
+    fn get_filter_title(&self) -> Option<String> {
+        let filter = self.filter_input.get_text();
+        if self.is_filter_prompt_open {
+            Some(format!(" filter: {filter}_ "))
+        } else if !filter.is_empty() {
+            Some(format!(" filter: {filter} "))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BodyState;
+
+    #[test]
+    fn drop_first_lines_keeps_prose_scroll_and_marks_on_their_lines() {
+        let mut state = BodyState::new();
+        state.prose_scroll = 10;
+        state.prose_max_scroll = 20;
+        state.marks.mark_all([1, 4]);
+
+        state.drop_first_lines(3);
+        assert_eq!(state.prose_scroll, 7);
+        assert_eq!(state.prose_max_scroll, 17);
+        assert_eq!(state.marks.get_line_indices(), vec![1]);
+
+        state.drop_first_lines(100);
+        assert_eq!(state.prose_scroll, 0);
+    }
 }
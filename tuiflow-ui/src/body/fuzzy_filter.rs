@@ -0,0 +1,45 @@
+/// Matches the characters of `pattern` in order, but not necessarily adjacent, against `line` ignoring case.
+/// Returns the char indices of the matched characters in `line` or `None` if `line` does not match.
+pub fn fuzzy_match(pattern: &str, line: &str) -> Option<Vec<usize>> {
+    let mut pattern_chars = pattern.chars().flat_map(char::to_lowercase).peekable();
+    let mut matched_char_indices = vec![];
+    for (idx, c) in line.chars().enumerate() {
+        let Some(pattern_char) = pattern_chars.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(std::iter::once(*pattern_char)) {
+            matched_char_indices.push(idx);
+            pattern_chars.next();
+        }
+    }
+
+    match pattern_chars.peek() {
+        Some(_) => None,
+        None => Some(matched_char_indices),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::fuzzy_match;
+
+    #[test]
+    fn fuzzy_match_with_empty_pattern_matches_without_indices() {
+        assert_eq!(fuzzy_match("", "nginx-7d9f"), Some(vec![]));
+    }
+
+    #[test]
+    fn fuzzy_match_with_scattered_pattern_returns_matched_indices() {
+        assert_eq!(fuzzy_match("ngx", "nginx-7d9f"), Some(vec![0, 1, 4]));
+    }
+
+    #[test]
+    fn fuzzy_match_ignores_case() {
+        assert_eq!(fuzzy_match("NgX", "nginx-7d9f"), Some(vec![0, 1, 4]));
+    }
+
+    #[test]
+    fn fuzzy_match_with_pattern_out_of_order_returns_none() {
+        assert_eq!(fuzzy_match("xn", "nginx-7d9f"), None);
+    }
+}
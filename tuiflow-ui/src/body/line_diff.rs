@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tuiflow_model_contracts::display::{Display, Line};
+
+const HIGHLIGHT_DURATION: Duration = Duration::from_secs(3);
+
+/// What the last refresh of a state changed, highlighted for a moment after the refresh.
+pub struct LineChanges {
+    changed_line_indices: Vec<usize>,
+    removed_line_count: usize,
+    changed_at: Instant,
+}
+
+impl LineChanges {
+    pub fn new(old_display: &Display, new_display: &Display) -> Self {
+        let (changed_line_indices, removed_line_count) = diff_lines(old_display, new_display);
+        Self {
+            changed_line_indices,
+            removed_line_count,
+            changed_at: Instant::now(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changed_line_indices.is_empty() && self.removed_line_count == 0
+    }
+
+    pub fn is_changed(&self, line_index: usize) -> bool {
+        self.is_recent() && self.changed_line_indices.contains(&line_index)
+    }
+
+    /// The number of removed lines while the changes are highlighted, 0 afterwards.
+    pub fn get_removed_line_count(&self) -> usize {
+        match self.is_recent() {
+            true => self.removed_line_count,
+            false => 0,
+        }
+    }
+
+    fn is_recent(&self) -> bool {
+        self.changed_at.elapsed() < HIGHLIGHT_DURATION
+    }
+}
+
+/// The lines of the new display by their item key, built once per refresh to find the items of the old lines.
+pub struct ItemLines<'a> {
+    line_indices: HashMap<&'a str, usize>, // the first line of each item
+}
+
+impl<'a> ItemLines<'a> {
+    pub fn new(new_display: &'a Display) -> Self {
+        let mut line_indices = HashMap::new();
+        for line_index in 0..new_display.lines.len() {
+            if let Some(item_key) = new_display.get_item_key(line_index) {
+                line_indices.entry(item_key).or_insert(line_index);
+            }
+        }
+        Self { line_indices }
+    }
+
+    /// Finds the line of the new display that shows the item of the old line, the line with the same item key
+    /// (e.g. the pod `api-1` whose status changed if the pod name is the key variable of the state).
+    pub fn find_same_item(&self, old_display: &Display, old_line_index: usize) -> Option<usize> {
+        let old_item_key = old_display.get_item_key(old_line_index)?;
+        self.line_indices.get(old_item_key).copied()
+    }
+}
+
+/// The indices of the new lines that were not there before and the number of old items that are gone.
+pub fn diff_lines(old_display: &Display, new_display: &Display) -> (Vec<usize>, usize) {
+    let (old_lines, new_lines): (&[Line], &[Line]) = (&old_display.lines, &new_display.lines);
+    let mut old_line_counts: HashMap<&str, usize> = HashMap::new();
+    old_lines
+        .iter()
+        .for_each(|line| *old_line_counts.entry(line.0.as_str()).or_default() += 1);
+
+    let changed_line_indices = new_lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| match old_line_counts.get_mut(line.0.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    // a changed line still shows its item, only lines whose item is gone count as removed
+    let item_lines = ItemLines::new(new_display);
+    let removed_line_count = (0..old_lines.len())
+        .filter(|old_line_index| item_lines.find_same_item(old_display, *old_line_index).is_none())
+        .count();
+    (changed_line_indices, removed_line_count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff_lines, ItemLines};
+    use tuiflow_model_contracts::display::{Display, Line};
+
+    // the pod name is the item key of each line
+    fn pods(lines: &[&str]) -> Display {
+        Display {
+            lines: lines.iter().map(|line| Line::from(*line)).collect(),
+            item_keys: lines.iter().map(|line| line.split_whitespace().next().unwrap().to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn find_same_item_finds_line_with_same_item_key() {
+        let old_display = pods(&["api-1 Running", "api-2 Pending", "api-3 Running"]);
+        let new_display = pods(&["api-2 Running", "api-1 CrashLoopBackOff"]);
+        let item_lines = ItemLines::new(&new_display);
+        assert_eq!(item_lines.find_same_item(&old_display, 0), Some(1));
+        assert_eq!(item_lines.find_same_item(&old_display, 1), Some(0));
+        assert_eq!(item_lines.find_same_item(&old_display, 2), None);
+    }
+
+    #[test]
+    fn find_same_item_without_item_keys_finds_same_line_only() {
+        let old_display = Display { lines: vec!["api-1 Running".into()], item_keys: vec![] };
+        let new_display = Display { lines: vec!["api-1 Error".into(), "api-1 Running".into()], item_keys: vec![] };
+        assert_eq!(ItemLines::new(&new_display).find_same_item(&old_display, 0), Some(1));
+    }
+
+    #[test]
+    fn diff_lines_finds_new_and_changed_lines_and_counts_removed_items() {
+        let old_display = pods(&["api-1 Running", "api-2 Running", "db-1 Running"]);
+        let new_display = pods(&["api-1 Running", "api-2 Error", "web-1 Running"]);
+        assert_eq!(diff_lines(&old_display, &new_display), (vec![1, 2], 1));
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeSet;
+
+/// The lines marked to run a control for all of them at once, by their index in the display.
+#[derive(Default)]
+pub struct LineMarks {
+    line_indices: BTreeSet<usize>,
+}
+
+impl LineMarks {
+    pub fn toggle(&mut self, line_index: usize) {
+        if !self.line_indices.remove(&line_index) {
+            self.line_indices.insert(line_index);
+        }
+    }
+
+    pub fn mark_all(&mut self, line_indices: impl IntoIterator<Item = usize>) {
+        self.line_indices.extend(line_indices);
+    }
+
+    pub fn invert(&mut self, line_indices: impl IntoIterator<Item = usize>) {
+        line_indices.into_iter().for_each(|line_index| self.toggle(line_index));
+    }
+
+    /// Moves the marks along when the first lines of the display were dropped.
+    pub fn shift(&mut self, dropped_line_count: usize) {
+        self.line_indices = self
+            .line_indices
+            .iter()
+            .filter_map(|line_index| line_index.checked_sub(dropped_line_count))
+            .collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.line_indices.clear();
+    }
+
+    pub fn is_marked(&self, line_index: usize) -> bool {
+        self.line_indices.contains(&line_index)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.line_indices.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.line_indices.len()
+    }
+
+    /// The marked lines in display order.
+    pub fn get_line_indices(&self) -> Vec<usize> {
+        self.line_indices.iter().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LineMarks;
+
+    #[test]
+    fn toggle_marks_and_unmarks_line() {
+        let mut marks = LineMarks::default();
+        marks.toggle(2);
+        marks.toggle(0);
+        assert_eq!(marks.get_line_indices(), vec![0, 2]);
+
+        marks.toggle(2);
+        assert_eq!(marks.get_line_indices(), vec![0]);
+    }
+
+    #[test]
+    fn shift_forgets_marks_of_dropped_lines() {
+        let mut marks = LineMarks::default();
+        marks.mark_all([0, 2, 5]);
+        marks.shift(2);
+        assert_eq!(marks.get_line_indices(), vec![0, 3]);
+    }
+
+    #[test]
+    fn invert_toggles_only_given_lines() {
+        let mut marks = LineMarks::default();
+        marks.mark_all([1, 3]);
+        marks.invert([0, 1, 2]);
+        assert_eq!(marks.get_line_indices(), vec![0, 2, 3]);
+    }
+}
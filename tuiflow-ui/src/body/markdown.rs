@@ -0,0 +1,127 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use tuiflow_model_contracts::display;
+
+/// Styles the most common markdown elements: headings, lists, quotes, code blocks and inline emphasis.
+/// Everything else is shown as is.
+pub fn render_markdown(lines: &[display::Line]) -> Vec<Line<'_>> {
+    let mut is_code_block = false;
+    lines
+        .iter()
+        .map(|line| {
+            let line = line.0.as_str();
+            if line.trim_start().starts_with("```") {
+                is_code_block = !is_code_block;
+                return Line::styled(line, code_style());
+            }
+            if is_code_block {
+                return Line::styled(line, code_style());
+            }
+            render_markdown_line(line)
+        })
+        .collect()
+}
+
+fn render_markdown_line(line: &str) -> Line<'_> {
+    let trimmed = line.trim_start();
+    let heading_level = trimmed.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&heading_level) && trimmed[heading_level..].starts_with(' ') {
+        let mut heading_style = Style::new().add_modifier(Modifier::BOLD).fg(Color::Cyan);
+        if heading_level == 1 {
+            heading_style = heading_style.add_modifier(Modifier::UNDERLINED);
+        }
+        return Line::styled(trimmed[heading_level..].trim_start(), heading_style);
+    }
+
+    let indentation = &line[..line.len() - trimmed.len()];
+    if let Some(item) = trimmed.strip_prefix("- ").or(trimmed.strip_prefix("* ")) {
+        let mut spans = vec![Span::raw(indentation), Span::raw("• ")];
+        spans.extend(render_inline(item));
+        return Line::from(spans);
+    }
+    if let Some(quote) = trimmed.strip_prefix('>') {
+        let quote_style = Style::new().add_modifier(Modifier::ITALIC).fg(Color::Gray);
+        return Line::from(vec![
+            Span::raw(indentation),
+            Span::styled("│ ", quote_style),
+            Span::styled(quote.trim_start(), quote_style),
+        ]);
+    }
+
+    Line::from(render_inline(line))
+}
+
+fn render_inline(text: &str) -> Vec<Span<'_>> {
+    let mut spans = vec![];
+    let mut rest = text;
+    while !rest.is_empty() {
+        let next_marker = ["`", "**", "*"]
+            .iter()
+            .filter_map(|marker| {
+                let start = rest.find(marker)?;
+                let end = rest[start + marker.len()..].find(marker)? + start + marker.len();
+                (end > start + marker.len()).then_some((start, end, *marker))
+            })
+            .min_by_key(|(start, _, marker)| (*start, usize::MAX - marker.len()));
+
+        let Some((start, end, marker)) = next_marker else {
+            spans.push(Span::raw(rest));
+            break;
+        };
+        if start > 0 {
+            spans.push(Span::raw(&rest[..start]));
+        }
+        let content = &rest[start + marker.len()..end];
+        let style = match marker {
+            "`" => code_style(),
+            "**" => Style::new().add_modifier(Modifier::BOLD),
+            _ => Style::new().add_modifier(Modifier::ITALIC),
+        };
+        spans.push(Span::styled(content, style));
+        rest = &rest[end + marker.len()..];
+    }
+    spans
+}
+
+fn code_style() -> Style {
+    Style::new().fg(Color::Green)
+}
+
+#[cfg(test)]
+mod test {
+    use super::render_markdown;
+    use ratatui::style::Modifier;
+    use tuiflow_model_contracts::display;
+
+    fn to_display_lines(lines: &[&str]) -> Vec<display::Line> {
+        lines.iter().map(|line| display::Line::from(*line)).collect()
+    }
+
+    #[test]
+    fn render_markdown_strips_heading_markers() {
+        let lines = to_display_lines(&["## Usage"]);
+
+        let rendered = render_markdown(&lines);
+        assert_eq!(rendered[0].to_string(), "Usage");
+        assert!(rendered[0].style.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn render_markdown_styles_inline_emphasis_without_markers() {
+        let lines = to_display_lines(&["run `ls` **now** or *later*"]);
+
+        let rendered = render_markdown(&lines);
+        assert_eq!(rendered[0].to_string(), "run ls now or later");
+        assert!(rendered[0].spans[3].style.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn render_markdown_keeps_code_blocks_as_is() {
+        let lines = to_display_lines(&["```", "# not a heading", "```"]);
+
+        let rendered = render_markdown(&lines);
+        assert_eq!(rendered[1].to_string(), "# not a heading");
+    }
+}
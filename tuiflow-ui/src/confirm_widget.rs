@@ -0,0 +1,80 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Widget, WidgetRef, Wrap},
+};
+use tuiflow_model_contracts::control::Key;
+
+const DIALOG_WIDTH: u16 = 60;
+
+/// A control that waits for the user to confirm it before it runs.
+pub struct PendingConfirmation {
+    key: Key,                 // the control that runs once confirmed
+    message: String,
+    marked_line_count: usize, // 0 if the control runs for the selected line
+}
+
+impl PendingConfirmation {
+    pub fn new(key: Key, message: String, marked_line_count: usize) -> Self {
+        Self {
+            key,
+            message,
+            marked_line_count,
+        }
+    }
+
+    pub fn get_key(&self) -> Key {
+        self.key
+    }
+
+    pub fn is_yes(key: &Key) -> bool {
+        *key == Key::Char('y') || *key == Key::Enter
+    }
+
+    pub fn is_no(key: &Key) -> bool {
+        *key == Key::Char('n') || *key == Key::Esc
+    }
+}
+
+/// A yes/no dialog over the middle of the area, answered before the confirmed control runs.
+pub struct ConfirmWidget<'a> {
+    confirmation: &'a PendingConfirmation,
+}
+
+impl<'a> ConfirmWidget<'a> {
+    pub fn new(confirmation: &'a PendingConfirmation) -> Self {
+        Self { confirmation }
+    }
+}
+
+impl<'a> WidgetRef for ConfirmWidget<'a> {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let title = match self.confirmation.marked_line_count {
+            0 => " confirm ".to_string(),
+            marked_line_count => format!(" confirm for {marked_line_count} marked lines "),
+        };
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::new().fg(Color::Yellow))
+            .title(Line::styled(title, Style::new().add_modifier(Modifier::BOLD)))
+            .title_bottom(Line::from(" y: yes · n: no ").right_aligned());
+        let message = Paragraph::new(self.confirmation.message.as_str()).wrap(Wrap { trim: false });
+
+        let width = DIALOG_WIDTH.min(area.width);
+        let height = (message.line_count(width.saturating_sub(2)) as u16 + 2).min(area.height);
+        let [dialog_area] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [dialog_area] = Layout::horizontal([Constraint::Length(width)])
+            .flex(Flex::Center)
+            .areas(dialog_area);
+
+        Clear.render(dialog_area, buf);
+        let message_area = block.inner(dialog_area);
+        block.render_ref(dialog_area, buf);
+        message.render_ref(message_area, buf);
+    }
+}
@@ -1,13 +1,16 @@
 use crate::io::InputUpdatedViewModel;
 use crate::key_control_view_model::KeyControlViewModel;
+use crate::reserved_controls::ReservedControls;
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Layout, Rect},
+    style::{Color, Style},
     widgets::{Block, BorderType, Borders, Paragraph, WidgetRef},
 };
 use tuiflow_model::state::Transit;
 use tuiflow_model::workflow::Workflow;
-use tuiflow_model_contracts::control::{Control, Key};
+use tuiflow_model_contracts::control::Key;
+use tuiflow_model_contracts::state_kind::StateKind;
 use tuiflow_model_contracts::terminal_flow::TerminalFlow;
 
 #[derive(Clone)]
@@ -18,14 +21,23 @@ pub struct ControlsWidget<'a> {
 
 impl<'a> ControlsWidget<'a> {
     pub fn new(view_model: &ControlsViewModel) -> Self {
+        let main_block = Block::new()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+
         Self {
-            main_block: Block::new()
-                .borders(Borders::ALL)
-                .border_type(BorderType::Rounded),
+            main_block,
             entries: view_model
                 .entries
                 .iter()
-                .map(|entry| Paragraph::new(String::from(entry)))
+                .map(|entry| {
+                    let paragraph = Paragraph::new(String::from(entry));
+                    if entry.is_applicable() {
+                        paragraph
+                    } else {
+                        paragraph.style(Style::new().fg(Color::DarkGray))
+                    }
+                })
                 .collect::<Vec<Paragraph<'a>>>(),
         }
     }
@@ -71,26 +83,54 @@ impl<'a> WidgetRef for ControlsWidget<'a> {
 
 pub struct ControlsViewModel {
     entries: Vec<KeyControlViewModel>,
-    selection_up: Control,
-    selection_down: Control,
+    reserved_controls: ReservedControls,
+    selected_line_index: Option<usize>,
+    input: Option<String>, // the typed text of an input state
 }
 
 impl ControlsViewModel {
-    pub fn new<T: Transit>(
-        workflow: &Workflow<T>,
-        selection_up: Control,
-        selection_down: Control,
-    ) -> Self {
-        let mut entries: Vec<KeyControlViewModel> = workflow
+    pub fn new<T: Transit>(workflow: &Workflow<T>, reserved_controls: ReservedControls) -> Self {
+        let selected_line_index = (!workflow.get_display().lines.is_empty()).then_some(0);
+        let input = matches!(workflow.get_state_kind(), StateKind::Input { .. }).then_some("");
+        let mut view_model = Self {
+            entries: vec![],
+            reserved_controls,
+            selected_line_index,
+            input: None,
+        };
+        view_model.refresh(workflow, selected_line_index, input);
+        view_model
+    }
+
+    /// Lists the controls of the current state, greying out the ones that can't run for the selected line
+    /// or the typed text.
+    pub fn refresh(&mut self, workflow: &impl TerminalFlow, selected_line_index: Option<usize>, input: Option<&str>) {
+        self.selected_line_index = selected_line_index;
+        self.input = input.map(str::to_string);
+        let applicable_controls = workflow.get_applicable_state_controls(selected_line_index, input);
+        self.entries = workflow
             .get_state_controls()
             .into_iter()
-            .map(|control| KeyControlViewModel::new(control))
+            .map(|control| {
+                let is_applicable = applicable_controls.contains(&control);
+                KeyControlViewModel::new(control).with_applicable(is_applicable)
+            })
             .collect();
 
-        entries.push(KeyControlViewModel::new(selection_up.clone()));
-        entries.push(KeyControlViewModel::new(selection_down.clone()));
-
-        Self { entries, selection_up, selection_down}
+        let reserved_controls = &self.reserved_controls;
+        self.entries.push(KeyControlViewModel::new(reserved_controls.selection_up.clone()));
+        self.entries.push(KeyControlViewModel::new(reserved_controls.selection_down.clone()));
+        self.entries.push(KeyControlViewModel::new(reserved_controls.filter.clone()));
+        let has_history = workflow.get_breadcrumb().len() > 1;
+        self.entries.push(KeyControlViewModel::new(reserved_controls.back.clone()).with_applicable(has_history));
+        self.entries.push(
+            KeyControlViewModel::new(reserved_controls.refresh.clone()).with_applicable(workflow.is_refreshable()),
+        );
+        let can_mark = workflow.get_state_kind() == StateKind::Lines;
+        [&reserved_controls.toggle_mark, &reserved_controls.mark_all, &reserved_controls.invert_marks]
+            .into_iter()
+            .for_each(|control| self.entries.push(KeyControlViewModel::new(control.clone()).with_applicable(can_mark)));
+        self.entries.push(KeyControlViewModel::new(reserved_controls.error_log.clone()));
     }
 }
 
@@ -112,13 +152,7 @@ impl InputUpdatedViewModel for ControlsViewModel {
         workflow: &mut impl TerminalFlow,
         _: &Key,
     ) {
-        self.entries = workflow
-            .get_state_controls()
-            .into_iter()
-            .map(|control| KeyControlViewModel::new(control))
-            .collect();
-
-        self.entries.push(KeyControlViewModel::new(self.selection_up.clone()));
-        self.entries.push(KeyControlViewModel::new(self.selection_down.clone()));
+        let input = self.input.take();
+        self.refresh(workflow, self.selected_line_index, input.as_deref());
     }
 }
@@ -0,0 +1,344 @@
+use crate::reserved_controls::ReservedControls;
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Widget, WidgetRef, Wrap},
+};
+use std::collections::VecDeque;
+use std::time::Instant;
+use tuiflow_model_contracts::command_runner::CommandRunnerError;
+use tuiflow_model_contracts::control::Key;
+use tuiflow_model_contracts::error::StateTransitionError;
+
+const MAX_LOGGED_ERRORS: usize = 50;
+const DIALOG_WIDTH: u16 = 72;
+
+/// A failure kept in the error log, with the details of the command if one failed.
+pub struct ErrorEntry {
+    message: String,
+    command_error: Option<CommandRunnerError>,
+    occurred_at: Instant,
+}
+
+impl ErrorEntry {
+    fn new(error: &StateTransitionError) -> Self {
+        let command_error = match error {
+            StateTransitionError::CommandExecutionError(command_error) => Some(command_error.clone()),
+            _ => None,
+        };
+        Self {
+            message: error.to_string(),
+            command_error,
+            occurred_at: Instant::now(),
+        }
+    }
+
+    /// Whether the details of the error are worth opening right away, a cancelled command was the user's choice.
+    fn has_command_failed(&self) -> bool {
+        self.command_error
+            .as_ref()
+            .is_some_and(|command_error| !command_error.is_cancelled())
+    }
+
+    fn get_detail_lines(&self) -> Vec<Line<'_>> {
+        let label_style = Style::new().add_modifier(Modifier::BOLD);
+        let mut lines = vec![Line::styled(self.message.as_str(), Style::new().fg(Color::Red))];
+        let Some(command_error) = &self.command_error else {
+            return lines;
+        };
+        lines.push(Line::from(vec![
+            Span::styled("command: ", label_style),
+            Span::raw(command_error.get_command()),
+        ]));
+        let Some(output) = command_error.get_output() else {
+            return lines;
+        };
+        lines.push(Line::from(vec![
+            Span::styled("exit status: ", label_style),
+            Span::raw(format!("{} after {:.1}s", output.exit_status, output.duration.as_secs_f32())),
+        ]));
+        if !output.stderr.trim().is_empty() {
+            lines.push(Line::styled("stderr:", label_style));
+            lines.extend(output.stderr.lines().map(Line::raw));
+        }
+        lines
+    }
+}
+
+/// The dialog of the error log that is open over the flow.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorDialog {
+    Details,             // the details of the latest error
+    Log { scroll: u16 }, // all logged errors, the newest first
+}
+
+pub struct ErrorViewModel {
+    entries: VecDeque<ErrorEntry>, // the newest first
+    is_latest_shown: bool,         // the status bar shows the latest error until the next control runs
+    toast: Option<String>,         // the output of a side effect, shown in the status bar until the next control runs
+    error_log: Key,
+    selection_up: Key,
+    selection_down: Key,
+}
+
+impl ErrorViewModel {
+    pub fn new(reserved_controls: &ReservedControls) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            is_latest_shown: false,
+            toast: None,
+            error_log: reserved_controls.error_log.get_key(),
+            selection_up: reserved_controls.selection_up.get_key(),
+            selection_down: reserved_controls.selection_down.get_key(),
+        }
+    }
+
+    /// Logs the error and shows it in the status bar, returns the dialog to open for a failed command.
+    pub fn report(&mut self, error: &StateTransitionError) -> Option<ErrorDialog> {
+        self.entries.push_front(ErrorEntry::new(error));
+        self.entries.truncate(MAX_LOGGED_ERRORS);
+        self.is_latest_shown = true;
+        self.entries[0].has_command_failed().then_some(ErrorDialog::Details)
+    }
+
+    /// Shows the message in the status bar instead of the latest error.
+    pub fn show_toast(&mut self, toast: String) {
+        self.is_latest_shown = false;
+        self.toast = Some(toast);
+    }
+
+    /// Hides the latest error or toast from the status bar, the error stays in the log.
+    pub fn clear_status(&mut self) {
+        self.is_latest_shown = false;
+        self.toast = None;
+    }
+
+    pub fn get_status(&self) -> Option<&str> {
+        self.entries
+            .front()
+            .filter(|_| self.is_latest_shown)
+            .map(|entry| entry.message.as_str())
+    }
+
+    pub fn is_error_log_key(&self, key: &Key) -> bool {
+        *key == self.error_log
+    }
+
+    pub fn is_dialog_key(&self, key: &Key) -> bool {
+        [self.error_log, self.selection_up, self.selection_down, Key::Esc, Key::Enter].contains(key)
+    }
+
+    /// The dialog after the key was pressed in it, `None` once it is closed.
+    pub fn update_dialog(&self, dialog: ErrorDialog, key: &Key) -> Option<ErrorDialog> {
+        match dialog {
+            _ if [self.error_log, Key::Esc, Key::Enter].contains(key) => None,
+            ErrorDialog::Log { scroll } if *key == self.selection_down => Some(ErrorDialog::Log {
+                scroll: scroll.saturating_add(1),
+            }),
+            ErrorDialog::Log { scroll } if *key == self.selection_up => Some(ErrorDialog::Log {
+                scroll: scroll.saturating_sub(1),
+            }),
+            dialog => Some(dialog),
+        }
+    }
+}
+
+/// A single row under the lines that shows the latest error or toast.
+pub struct StatusBarWidget<'a> {
+    status: Option<Line<'a>>,
+    hint: Line<'a>,
+}
+
+impl<'a> StatusBarWidget<'a> {
+    pub fn new(view_model: &'a ErrorViewModel) -> Self {
+        let status = match (view_model.get_status(), &view_model.toast) {
+            (Some(message), _) => Some(Line::styled(format!(" ✗ {message}"), Style::new().fg(Color::Red))),
+            (None, Some(toast)) => Some(Line::styled(format!(" ✓ {toast}"), Style::new().fg(Color::Green))),
+            (None, None) => None,
+        };
+        let hint = Line::styled(format!(" {}: error log ", view_model.error_log), Style::new().fg(Color::DarkGray));
+        Self { status, hint }
+    }
+
+    pub fn get_row_count(&self) -> u16 {
+        match self.status {
+            Some(_) => 1,
+            None => 0,
+        }
+    }
+}
+
+impl<'a> WidgetRef for StatusBarWidget<'a> {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let Some(status) = &self.status else {
+            return;
+        };
+        // the hint stays visible, a long message is cut off before it
+        let [status_area, hint_area] =
+            Layout::horizontal([Constraint::Min(0), Constraint::Length(self.hint.width() as u16)]).areas(area);
+        status.render_ref(status_area, buf);
+        self.hint.render_ref(hint_area, buf);
+    }
+}
+
+/// The details of the latest error or the whole error log over the middle of the area.
+pub struct ErrorDialogWidget<'a> {
+    view_model: &'a ErrorViewModel,
+    dialog: ErrorDialog,
+}
+
+impl<'a> ErrorDialogWidget<'a> {
+    pub fn new(view_model: &'a ErrorViewModel, dialog: ErrorDialog) -> Self {
+        Self { view_model, dialog }
+    }
+
+    fn build_log_lines(&self) -> Vec<Line<'a>> {
+        if self.view_model.entries.is_empty() {
+            return vec![Line::styled("No errors so far", Style::new().fg(Color::DarkGray))];
+        }
+        self.view_model
+            .entries
+            .iter()
+            .flat_map(|entry| {
+                let age = format!("{}s ago", entry.occurred_at.elapsed().as_secs());
+                let mut lines = vec![Line::styled(age, Style::new().fg(Color::DarkGray))];
+                lines.extend(entry.get_detail_lines());
+                lines.push(Line::raw(""));
+                lines
+            })
+            .collect()
+    }
+}
+
+impl<'a> WidgetRef for ErrorDialogWidget<'a> {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let (title, lines, scroll, hint) = match self.dialog {
+            ErrorDialog::Details => {
+                let lines = self
+                    .view_model
+                    .entries
+                    .front()
+                    .map(ErrorEntry::get_detail_lines)
+                    .unwrap_or_default();
+                (" error ".to_string(), lines, 0, " Esc: close ".to_string())
+            }
+            ErrorDialog::Log { scroll } => {
+                let title = format!(" error log ({}) ", self.view_model.entries.len());
+                let hint = format!(
+                    " {}/{}: scroll · Esc: close ",
+                    self.view_model.selection_down, self.view_model.selection_up
+                );
+                (title, self.build_log_lines(), scroll, hint)
+            }
+        };
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::new().fg(Color::Red))
+            .title(Line::styled(title, Style::new().add_modifier(Modifier::BOLD)))
+            .title_bottom(Line::from(hint).right_aligned());
+        let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false });
+
+        let width = DIALOG_WIDTH.min(area.width);
+        let line_count = paragraph.line_count(width.saturating_sub(2)) as u16;
+        let height = (line_count + 2).min(area.height.saturating_sub(2));
+        let scroll = scroll.min(line_count.saturating_sub(height.saturating_sub(2)));
+        let [dialog_area] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [dialog_area] = Layout::horizontal([Constraint::Length(width)])
+            .flex(Flex::Center)
+            .areas(dialog_area);
+
+        Clear.render(dialog_area, buf);
+        let content_area = block.inner(dialog_area);
+        block.render_ref(dialog_area, buf);
+        paragraph.scroll((scroll, 0)).render_ref(content_area, buf);
+    }
+}
+
+/// Fills the whole area with the reason the flow could not be built, until the user retries or quits.
+pub struct StartupErrorWidget<'a> {
+    message: &'a str,
+    retry: Key,
+    quit: Key,
+}
+
+impl<'a> StartupErrorWidget<'a> {
+    pub fn new(message: &'a str, retry: Key, quit: Key) -> Self {
+        Self { message, retry, quit }
+    }
+}
+
+impl<'a> WidgetRef for StartupErrorWidget<'a> {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::new().fg(Color::Red))
+            .title(Line::styled(" the flow could not start ", Style::new().add_modifier(Modifier::BOLD)))
+            .title_bottom(Line::from(format!(" {}: retry · {}: quit ", self.retry, self.quit)).right_aligned());
+        let content_area = block.inner(area);
+        block.render_ref(area, buf);
+        Paragraph::new(self.message)
+            .wrap(Wrap { trim: false })
+            .render_ref(content_area, buf);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ErrorDialog, ErrorViewModel, MAX_LOGGED_ERRORS};
+    use crate::reserved_controls::ReservedControls;
+    use tuiflow_model::Control;
+    use tuiflow_model_contracts::command_runner::CommandRunnerError;
+    use tuiflow_model_contracts::control::Key;
+    use tuiflow_model_contracts::error::StateTransitionError;
+
+    fn view_model() -> ErrorViewModel {
+        let control = |key| Control::new("control", key);
+        ErrorViewModel::new(&ReservedControls {
+            selection_up: control(Key::Char('k')),
+            selection_down: control(Key::Char('j')),
+            filter: control(Key::Char('/')),
+            back: control(Key::Esc),
+            toggle_mark: control(Key::Tab),
+            mark_all: control(Key::Char('*')),
+            invert_marks: control(Key::Char('!')),
+            error_log: control(Key::Char('E')),
+            refresh: control(Key::Char('R')),
+        })
+    }
+
+    #[test]
+    fn report_opens_details_only_for_failed_commands_and_caps_log() {
+        let mut view_model = view_model();
+        let command = "sh -c false".to_string();
+        let failed = CommandRunnerError::Spawn { command: command.clone(), reason: "No such file".to_string() };
+        let cancelled = CommandRunnerError::Cancelled { command };
+
+        assert_eq!(view_model.report(&StateTransitionError::HistoryEmpty), None);
+        assert_eq!(
+            view_model.report(&StateTransitionError::CommandExecutionError(failed)),
+            Some(ErrorDialog::Details)
+        );
+        assert_eq!(view_model.report(&StateTransitionError::CommandExecutionError(cancelled)), None);
+        (0..MAX_LOGGED_ERRORS).for_each(|_| _ = view_model.report(&StateTransitionError::HistoryEmpty));
+        assert_eq!(view_model.entries.len(), MAX_LOGGED_ERRORS);
+
+        view_model.clear_status();
+        assert_eq!(view_model.get_status(), None);
+    }
+
+    #[test]
+    fn update_dialog_scrolls_log_and_closes() {
+        let view_model = view_model();
+        let log = ErrorDialog::Log { scroll: 0 };
+        assert_eq!(view_model.update_dialog(log, &Key::Char('j')), Some(ErrorDialog::Log { scroll: 1 }));
+        assert_eq!(view_model.update_dialog(log, &Key::Char('k')), Some(log));
+        assert_eq!(view_model.update_dialog(log, &Key::Char('E')), None);
+        assert_eq!(view_model.update_dialog(ErrorDialog::Details, &Key::Esc), None);
+    }
+}
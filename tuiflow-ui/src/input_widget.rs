@@ -0,0 +1,148 @@
+use crate::io::InputUpdatedViewModel;
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Paragraph, StatefulWidgetRef, WidgetRef},
+};
+use tuiflow_model::state::Transit;
+use tuiflow_model::workflow::Workflow;
+use tuiflow_model_contracts::control::Key;
+use tuiflow_model_contracts::state_kind::StateKind;
+use tuiflow_model_contracts::terminal_flow::TerminalFlow;
+
+pub struct InputWidget<'a> {
+    main_block: Block<'a>,
+}
+
+impl<'a> InputWidget<'a> {
+    pub fn new(view_model: &'a InputViewModel) -> Option<Self> {
+        let prompt = view_model.prompt.as_ref()?;
+        Some(Self {
+            main_block: Block::new()
+                .title(prompt.as_str())
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded),
+        })
+    }
+}
+
+impl<'a> StatefulWidgetRef for InputWidget<'a> {
+    type State = InputState;
+    fn render_ref(&self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        self.main_block.render_ref(area, buf);
+        let (before_cursor, from_cursor) = state.text.split_at(state.get_cursor_byte_index());
+        let mut from_cursor_chars = from_cursor.chars();
+        let cursor_char = from_cursor_chars
+            .next()
+            .map(String::from)
+            .unwrap_or(String::from(" "));
+        let line = Line::from(vec![
+            Span::raw(before_cursor),
+            Span::styled(cursor_char, Style::new().add_modifier(Modifier::REVERSED)),
+            Span::raw(from_cursor_chars.as_str()),
+        ]);
+        Paragraph::new(line).render_ref(self.main_block.inner(area), buf);
+    }
+}
+
+pub struct InputViewModel {
+    prompt: Option<String>,
+}
+
+impl InputViewModel {
+    pub fn new<T: Transit>(workflow: &Workflow<T>) -> Self {
+        Self {
+            prompt: Self::get_prompt(workflow),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    pub fn is_text_editing_key(key: &Key) -> bool {
+        matches!(
+            key,
+            Key::Char(_) | Key::Backspace | Key::Delete | Key::Left | Key::Right | Key::Home | Key::End
+        )
+    }
+
+    pub fn refresh(&mut self, workflow: &impl TerminalFlow) {
+        self.prompt = Self::get_prompt(workflow);
+    }
+
+    fn get_prompt(workflow: &impl TerminalFlow) -> Option<String> {
+        match workflow.get_state_kind() {
+            StateKind::Input { prompt } => Some(prompt),
+            _ => None,
+        }
+    }
+}
+
+impl InputUpdatedViewModel for InputViewModel {
+    type ViewState = InputState;
+
+    fn needs_update(&self, _: &Self::ViewState, _: &impl TerminalFlow, key: &Key) -> bool {
+        self.is_active() && Self::is_text_editing_key(key)
+    }
+
+    fn update(&mut self, state: &mut Self::ViewState, workflow: &mut impl TerminalFlow, key: &Key) {
+        if self.needs_update(state, workflow, key) {
+            state.edit(key);
+        } else {
+            self.refresh(workflow);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InputState {
+    text: String,
+    cursor: usize, // in chars, not bytes
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    pub(crate) fn edit(&mut self, key: &Key) {
+        match key {
+            Key::Char(c) => {
+                self.text.insert(self.get_cursor_byte_index(), *c);
+                self.cursor += 1;
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.get_cursor_byte_index());
+            }
+            Key::Delete if self.cursor < self.text.chars().count() => {
+                self.text.remove(self.get_cursor_byte_index());
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.text.chars().count()),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.text.chars().count(),
+            _ => {}
+        }
+    }
+
+    fn get_cursor_byte_index(&self) -> usize {
+        self.text
+            .char_indices()
+            .nth(self.cursor)
+            .map(|(idx, _)| idx)
+            .unwrap_or(self.text.len())
+    }
+}
@@ -3,6 +3,13 @@ pub mod key_event_to_model_mapping;
 use tuiflow_model_contracts::control::Key;
 use tuiflow_model_contracts::terminal_flow::TerminalFlow;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyRouting {
+    Control,   // the key may trigger controls
+    TextInput, // the key edits the text input and never triggers controls
+    Dialog,    // the key answers the open dialog and never triggers controls
+}
+
 pub trait InputUpdatedViewModel {
     type ViewState;
     fn needs_update(&self, state: &Self::ViewState, workflow: & impl TerminalFlow, key: &Key) -> bool; //TODO Make more generic over R,M
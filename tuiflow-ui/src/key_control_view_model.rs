@@ -4,15 +4,28 @@ use tuiflow_model::Control;
 #[derive(Clone)]
 pub struct KeyControlViewModel {
     control: Control,
+    is_applicable: bool, // whether the control can run for the selected line
 }
 
 impl KeyControlViewModel {
     pub fn new(control: Control) -> Self {
-        Self { control }
+        Self {
+            control,
+            is_applicable: true,
+        }
+    }
+
+    pub fn with_applicable(mut self, is_applicable: bool) -> Self {
+        self.is_applicable = is_applicable;
+        self
+    }
+
+    pub fn is_applicable(&self) -> bool {
+        self.is_applicable
     }
 }
 
-impl<'a> From<&KeyControlViewModel> for String {
+impl From<&KeyControlViewModel> for String {
     fn from(value: &KeyControlViewModel) -> Self {
         format!("{}: {}", value.control.get_key(), value.control.get_name())
     }
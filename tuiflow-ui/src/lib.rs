@@ -1,6 +1,10 @@
 pub mod body;
+pub mod confirm_widget;
 pub mod title_bar_widget;
 pub mod controls_widget;
+pub mod error_widget;
+pub mod input_widget;
 pub mod key_control_view_model;
 pub mod main_widget;
+pub mod reserved_controls;
 pub mod io;
@@ -1,30 +1,43 @@
 use crate::body::{BodyState, BodyViewModel, BodyWidget};
+use crate::confirm_widget::{ConfirmWidget, PendingConfirmation};
 use crate::controls_widget::{ControlsViewModel, ControlsWidget, WIDGET_PADDING_VERTICAL};
-use crate::io::InputUpdatedViewModel;
-use crate::title_bar_widget::{TitleBarViewModel, TitleBarWidget};
+use crate::error_widget::{ErrorDialog, ErrorDialogWidget, ErrorViewModel, StatusBarWidget};
+use crate::input_widget::{InputState, InputViewModel, InputWidget};
+use crate::io::{InputUpdatedViewModel, KeyRouting};
+use crate::reserved_controls::ReservedControls;
+use crate::title_bar_widget::{TitleBarViewModel, TitleBarWidget, TITLE_BAR_ROW_COUNT};
 use ratatui::{buffer::Buffer, layout::{Constraint, Layout, Rect}, widgets::{StatefulWidgetRef, WidgetRef}};
 use tuiflow_model::state::Transit;
 use tuiflow_model::workflow::Workflow;
-use tuiflow_model::Control;
 use tuiflow_model_contracts::control::Key;
-use tuiflow_model_contracts::terminal_flow::TerminalFlow;
+use tuiflow_model_contracts::error::StateTransitionError;
+use tuiflow_model_contracts::output_stream::StreamUpdate;
+use tuiflow_model_contracts::terminal_flow::{ControlOutcome, TerminalFlow, TerminalHandover};
 
 pub struct MainWidget<'a> {
     title_bar: TitleBarWidget<'a>,
+    input: Option<InputWidget<'a>>,
     body: BodyWidget<'a>,
+    status_bar: StatusBarWidget<'a>,
     legend_footer: ControlsWidget<'a>,
+    error_view_model: &'a ErrorViewModel,
 }
 
 impl<'a> MainWidget<'a> {
     pub fn new(view_model: &'a MainViewModel) -> Self {
         let title_bar = TitleBarWidget::new(&view_model.title_bar_view_model);
+        let input = InputWidget::new(&view_model.input_view_model);
         let body = BodyWidget::new(&view_model.body_view_model);
+        let status_bar = StatusBarWidget::new(&view_model.error_view_model);
         let legend_footer = ControlsWidget::new(&view_model.legend_view_model);
 
         Self {
             title_bar,
+            input,
             body,
+            status_bar,
             legend_footer,
+            error_view_model: &view_model.error_view_model,
         }
     }
 }
@@ -34,67 +47,334 @@ impl<'a> StatefulWidgetRef for MainWidget<'a> {
     fn render_ref(&self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
         let legend_row_count =
             (self.legend_footer.get_legend_size() as f32 / 3.0).ceil() as u16 + WIDGET_PADDING_VERTICAL;
+        let input_row_count = if self.input.is_some() { 3 } else { 0 };
         let layout = Layout::vertical([
-            Constraint::Length(4),
+            Constraint::Length(TITLE_BAR_ROW_COUNT),
+            Constraint::Length(input_row_count),
             Constraint::Min(0),
+            Constraint::Length(self.status_bar.get_row_count()),
             Constraint::Length(legend_row_count),
         ]);
-        let [title_bar, body, legend] = layout.areas(area);
+        let [title_bar, input, body, status_bar, legend] = layout.areas(area);
         self.title_bar.render_ref(title_bar, buf);
+        if let Some(input_widget) = &self.input {
+            input_widget.render_ref(input, buf, &mut state.input_state);
+        }
         self.body.render_ref(body, buf, &mut state.body_state);
-        self.legend_footer.render_ref(legend, buf)
+        self.status_bar.render_ref(status_bar, buf);
+        self.legend_footer.render_ref(legend, buf);
+        if let Some(confirmation) = &state.confirmation {
+            ConfirmWidget::new(confirmation).render_ref(area, buf);
+        }
+        if let Some(error_dialog) = state.error_dialog {
+            ErrorDialogWidget::new(self.error_view_model, error_dialog).render_ref(area, buf);
+        }
     }
     
 }
 
 pub struct MainViewModel {
     title_bar_view_model: TitleBarViewModel,
+    input_view_model: InputViewModel,
     body_view_model: BodyViewModel,
     legend_view_model: ControlsViewModel,
+    error_view_model: ErrorViewModel,
+    back: Key,
+    refresh: Key,
 }
 
 impl MainViewModel {
-    pub fn new<T: Transit>(workflow: &Workflow<T>, selection_up: Control, selection_down: Control) -> Self {
+    pub fn new<T: Transit>(workflow: &Workflow<T>, reserved_controls: ReservedControls) -> Self {
         let display = workflow.get_display();
-        let select_up_key = selection_up.get_key();
-        let select_down_key = selection_down.get_key();
         Self {
             title_bar_view_model: TitleBarViewModel::new(workflow),
-            body_view_model: BodyViewModel::new(display.clone(), select_up_key, select_down_key),
-            legend_view_model: ControlsViewModel::new(workflow, selection_up, selection_down),
+            input_view_model: InputViewModel::new(workflow),
+            body_view_model: BodyViewModel::new(display.clone(), workflow.get_state_kind(), &reserved_controls),
+            back: reserved_controls.back.get_key(),
+            refresh: reserved_controls.refresh.get_key(),
+            error_view_model: ErrorViewModel::new(&reserved_controls),
+            legend_view_model: ControlsViewModel::new(workflow, reserved_controls),
+        }
+    }
+
+    pub fn route_key(&self, state: &MainState, key: &Key) -> KeyRouting {
+        if state.confirmation.is_some() || state.error_dialog.is_some() {
+            KeyRouting::Dialog
+        } else if self.body_view_model.is_filter_prompt_key(&state.body_state, key)
+            || (self.input_view_model.is_active() && InputViewModel::is_text_editing_key(key))
+        {
+            KeyRouting::TextInput
+        } else {
+            KeyRouting::Control
         }
     }
+
+    /// Shows the current state after it changed without a key press.
+    pub fn refresh(&mut self, state: &mut MainState, workflow: &impl TerminalFlow) {
+        state.input_state.clear();
+        self.body_view_model.refresh(&mut state.body_state, workflow);
+        self.title_bar_view_model.refresh(workflow);
+        self.input_view_model.refresh(workflow);
+        self.refresh_legend(state, workflow);
+    }
+
+    /// Greys out the controls of the legend that can't run for the selected line or the typed text.
+    fn refresh_legend(&mut self, state: &MainState, workflow: &impl TerminalFlow) {
+        let selected_line_index = self.body_view_model.get_selected_line_index(&state.body_state);
+        let input = self
+            .input_view_model
+            .is_active()
+            .then(|| state.input_state.get_text());
+        self.legend_view_model.refresh(workflow, selected_line_index, input);
+    }
+
+    /// Logs the failures of transitions that ran without a key press, e.g. auto transitions or refreshes.
+    pub fn report_background_errors(&mut self, background_errors: &[StateTransitionError]) {
+        background_errors.iter().for_each(|error| _ = self.error_view_model.report(error));
+    }
+
+    /// Shows the lines a streaming state received without a key press.
+    pub fn append_streamed_lines(&mut self, state: &mut MainState, workflow: &impl TerminalFlow, stream_update: StreamUpdate) {
+        self.body_view_model.append_lines(&mut state.body_state, workflow, stream_update);
+        self.refresh_legend(state, workflow);
+    }
+
+    /// Shows the lines of the state after its refresh interval rebuilt it.
+    pub fn refresh_lines(&mut self, state: &mut MainState, workflow: &impl TerminalFlow) {
+        self.body_view_model.refresh_lines(&mut state.body_state, workflow);
+        self.refresh_legend(state, workflow);
+    }
+
+    /// Whether a dialog waits for an answer, the flow keeps still meanwhile.
+    pub fn is_dialog_open(&self, state: &MainState) -> bool {
+        state.confirmation.is_some() || state.error_dialog.is_some()
+    }
+
+    /// Whether handling the key may run a command, so that the flow shows it as busy meanwhile.
+    pub fn may_run_command(&self, state: &MainState, workflow: &impl TerminalFlow, key: &Key) -> bool {
+        match self.route_key(state, key) {
+            KeyRouting::Dialog => state.confirmation.is_some() && PendingConfirmation::is_yes(key),
+            KeyRouting::TextInput => false,
+            // going back reruns the command of states refreshed on back
+            KeyRouting::Control => {
+                *key == self.back
+                    || *key == self.refresh
+                    || workflow.get_state_controls().iter().any(|control| control.get_key() == *key)
+            }
+        }
+    }
+
+    /// Whether the control hands the terminal over to an interactive program, so it must be released first.
+    pub fn get_terminal_handover(&self, state: &MainState, workflow: &impl TerminalFlow, key: &Key) -> Option<TerminalHandover> {
+        match (self.route_key(state, key), &state.confirmation) {
+            (KeyRouting::Dialog, Some(_)) if PendingConfirmation::is_yes(key) => return workflow.get_held_terminal_handover(),
+            (KeyRouting::Control, _) if self.get_confirmation(state, workflow, key).is_none() => {}
+            _ => return None,
+        }
+        let selected_line_index = self.body_view_model.get_selected_line_index(&state.body_state);
+        let marked_line_indices = self.body_view_model.get_marked_line_indices(&state.body_state);
+        if self.input_view_model.is_active() {
+            workflow.get_terminal_handover(selected_line_index, Some(state.input_state.get_text()), key)
+        } else if !marked_line_indices.is_empty() {
+            workflow.get_marked_terminal_handover(&marked_line_indices, key)
+        } else {
+            workflow.get_terminal_handover(selected_line_index, None, key)
+        }
+    }
+
+    /// The question to answer before the control of the key runs for the selected or marked lines.
+    fn get_confirmation(&self, state: &MainState, workflow: &impl TerminalFlow, key: &Key) -> Option<PendingConfirmation> {
+        let selected_line_index = self.body_view_model.get_selected_line_index(&state.body_state);
+        let marked_line_indices = self.body_view_model.get_marked_line_indices(&state.body_state);
+        if self.input_view_model.is_active() {
+            let message = workflow.get_confirmation(selected_line_index, Some(state.input_state.get_text()), key)?;
+            Some(PendingConfirmation::new(*key, message, 0))
+        } else if !marked_line_indices.is_empty() {
+            let message = workflow.get_marked_confirmation(&marked_line_indices, key)?;
+            Some(PendingConfirmation::new(*key, message, marked_line_indices.len()))
+        } else {
+            let message = workflow.get_confirmation(selected_line_index, None, key)?;
+            Some(PendingConfirmation::new(*key, message, 0))
+        }
+    }
+
+    /// Runs the control that hands the terminal over, after its confirmation if it asked for one.
+    /// Returns its failure as well, since the flow can't show it once the terminal was handed over for good.
+    pub fn hand_over(&mut self, state: &mut MainState, workflow: &mut impl TerminalFlow, key: &Key) -> Result<(), StateTransitionError> {
+        let (key, control_result) = match state.confirmation.take() {
+            Some(confirmation) => (confirmation.get_key(), self.run_held_control(state, workflow)),
+            None => (*key, self.run_control(state, workflow, key)),
+        };
+        self.refresh_after_control(state, workflow, &key);
+        control_result
+    }
+
+    /// Keeps the lines the control of the key runs for, so that it runs for them once its confirmation is answered.
+    fn hold_control(&self, state: &MainState, workflow: &mut impl TerminalFlow, key: &Key) {
+        let selected_line_index = self.body_view_model.get_selected_line_index(&state.body_state);
+        let marked_line_indices = self.body_view_model.get_marked_line_indices(&state.body_state);
+        let input = self.input_view_model.is_active().then(|| state.input_state.get_text());
+        // typed input runs the control for the selected line even with marks
+        let marked_line_indices = if input.is_some() { &[][..] } else { &marked_line_indices[..] };
+        workflow.hold_control(selected_line_index, marked_line_indices, input, key);
+    }
+
+    fn run_held_control(&mut self, state: &mut MainState, workflow: &mut impl TerminalFlow) -> Result<(), StateTransitionError> {
+        let transition_result = workflow.run_held_control();
+        self.show_control_outcome(state, workflow, transition_result)
+    }
+
+    /// Runs the control of the key for the typed input, the marked lines or the selected line.
+    /// A failure is reported to the user and returned.
+    fn run_control(&mut self, state: &mut MainState, workflow: &mut impl TerminalFlow, key: &Key) -> Result<(), StateTransitionError> {
+        let selected_line_index = self.body_view_model.get_selected_line_index(&state.body_state);
+        let marked_line_indices = self.body_view_model.get_marked_line_indices(&state.body_state);
+        let transition_result = if self.input_view_model.is_active() {
+            workflow.run_control_with_input(selected_line_index, state.input_state.get_text(), key)
+        } else if !marked_line_indices.is_empty() {
+            workflow.run_control_on_marked(&marked_line_indices, key)
+        } else {
+            workflow.run_control(selected_line_index, key)
+        };
+        self.show_control_outcome(state, workflow, transition_result)
+    }
+
+    fn show_control_outcome(
+        &mut self,
+        state: &mut MainState,
+        workflow: &mut impl TerminalFlow,
+        transition_result: Result<ControlOutcome, StateTransitionError>,
+    ) -> Result<(), StateTransitionError> {
+        match transition_result {
+            Ok(ControlOutcome::Transitioned) => state.input_state.clear(),
+            // the flow stayed in the state, so the selection stays on its item
+            Ok(ControlOutcome::Refreshed { toast }) => {
+                state.input_state.clear();
+                self.body_view_model.refresh_lines(&mut state.body_state, workflow);
+                if let Some(toast) = toast {
+                    self.error_view_model.show_toast(toast);
+                }
+            }
+            Err(e) => {
+                state.error_dialog = self.error_view_model.report(&e);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Lets every part show the state after the key was handled.
+    fn refresh_after_control(&mut self, state: &mut MainState, workflow: &mut impl TerminalFlow, key: &Key) {
+        self.body_view_model.update(&mut state.body_state, workflow, key);
+        self.title_bar_view_model.update(&mut (), workflow, key);
+        self.input_view_model.update(&mut state.input_state, workflow, key);
+        self.refresh_legend(state, workflow);
+        self.report_background_errors(&workflow.take_background_errors());
+    }
 }
 
 //TODO Make this all more condiitional: e.g. only recreate part x when part x actually changes
 impl InputUpdatedViewModel for MainViewModel {
     type ViewState = MainState;
     fn needs_update(&self, state: &Self::ViewState, workflow: &impl TerminalFlow, key: &Key) -> bool {
-        workflow.get_state_controls().iter().any(|control| control.get_key() == *key) 
+        if state.confirmation.is_some() {
+            return PendingConfirmation::is_yes(key) || PendingConfirmation::is_no(key);
+        }
+        if state.error_dialog.is_some() {
+            return self.error_view_model.is_dialog_key(key);
+        }
+        workflow.get_state_controls().iter().any(|control| control.get_key() == *key)
+        || *key == self.back
+        || *key == self.refresh
+        || self.error_view_model.is_error_log_key(key)
         || self.body_view_model.needs_update(&state.body_state, workflow, key)
         || self.legend_view_model.needs_update(&(), workflow, key)
         || self.title_bar_view_model.needs_update(&(), workflow, key)
+        || self.input_view_model.needs_update(&state.input_state, workflow, key)
     }
 
     fn update(&mut self, state: &mut Self::ViewState, workflow: &mut impl TerminalFlow, key: &Key) {
+        if let Some(confirmation) = state.confirmation.take() {
+            if PendingConfirmation::is_yes(key) {
+                _ = self.run_held_control(state, workflow);
+                self.refresh_after_control(state, workflow, &confirmation.get_key());
+            } else if !PendingConfirmation::is_no(key) {
+                state.confirmation = Some(confirmation);
+            }
+            return;
+        }
+        if let Some(error_dialog) = state.error_dialog {
+            state.error_dialog = self.error_view_model.update_dialog(error_dialog, key);
+            return;
+        }
+        if self.route_key(state, key) == KeyRouting::TextInput {
+            if state.body_state.is_filter_prompt_open() {
+                self.body_view_model.update(&mut state.body_state, workflow, key);
+            } else {
+                self.input_view_model.update(&mut state.input_state, workflow, key);
+            }
+            self.refresh_legend(state, workflow);
+            return;
+        }
+
+        self.error_view_model.clear_status();
+        if self.error_view_model.is_error_log_key(key) {
+            state.error_dialog = Some(ErrorDialog::Log { scroll: 0 });
+            return;
+        }
+        if *key == self.back {
+            match workflow.go_back() {
+                Ok(selected_line_index) => {
+                    self.refresh(state, workflow);
+                    self.body_view_model.select_line(&mut state.body_state, selected_line_index);
+                    self.refresh_legend(state, workflow);
+                }
+                Err(e) => state.error_dialog = self.error_view_model.report(&e),
+            }
+            return;
+        }
+        if *key == self.refresh {
+            match workflow.refresh() {
+                Ok(()) => self.refresh_lines(state, workflow),
+                Err(e) => state.error_dialog = self.error_view_model.report(&e),
+            }
+            return;
+        }
         if workflow.get_state_controls().iter().any(|control| control.get_key() == *key) {
-            _ = workflow.run_control(state.body_state.get_selected_line_index(), key);
+            // destructive controls wait for the answer to their question before they run
+            if let Some(confirmation) = self.get_confirmation(state, workflow, key) {
+                self.hold_control(state, workflow, key);
+                state.confirmation = Some(confirmation);
+                return;
+            }
+            _ = self.run_control(state, workflow, key);
         }
-        self.body_view_model.update(&mut state.body_state, workflow, key);
-        self.legend_view_model.update(&mut (), workflow, key);
-        self.title_bar_view_model.update(&mut (), workflow, key);
+        self.refresh_after_control(state, workflow, key);
     }
 }
 
 pub struct MainState {
     body_state: BodyState,
+    input_state: InputState,
+    confirmation: Option<PendingConfirmation>, // the control waiting for its confirmation dialog to be answered
+    error_dialog: Option<ErrorDialog>,
+}
+
+impl Default for MainState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MainState {
     pub fn new() -> Self {
         let body_state = BodyState::new();
+        let input_state = InputState::new();
         Self {
             body_state,
+            input_state,
+            confirmation: None,
+            error_dialog: None,
         }
     }
 }
\ No newline at end of file
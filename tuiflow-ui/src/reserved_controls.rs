@@ -0,0 +1,15 @@
+use tuiflow_model::Control;
+
+/// The controls the ui handles itself in every state, next to the controls of the flow.
+#[derive(Clone)]
+pub struct ReservedControls {
+    pub selection_up: Control,
+    pub selection_down: Control,
+    pub filter: Control,
+    pub back: Control,
+    pub toggle_mark: Control,
+    pub mark_all: Control,
+    pub invert_marks: Control,
+    pub error_log: Control,
+    pub refresh: Control,
+}
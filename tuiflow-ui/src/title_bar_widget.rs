@@ -1,5 +1,5 @@
 use crate::io::InputUpdatedViewModel;
-use ratatui::{buffer::Buffer, layout::{Constraint, Layout, Rect}, style::{Color, Style, Stylize}, widgets::{Paragraph, WidgetRef}};
+use ratatui::{buffer::Buffer, layout::{Constraint, Layout, Rect}, style::{Color, Style, Stylize}, widgets::{Clear, Paragraph, WidgetRef}};
 use tuiflow_model::state::Transit;
 use tuiflow_model::workflow::Workflow;
 use tuiflow_model_contracts::control::Key;
@@ -11,6 +11,8 @@ pub struct TitleBarWidget<'a> {
     state_title: Paragraph<'a>,
     logo: Paragraph<'a>,
 }
+pub const TITLE_BAR_ROW_COUNT: u16 = 4;
+
 const LOGO_STR: &str = 
 " _____  __  __  __  ____  __     ______  __    __
 /_  _/ / / / / / / / __/ / /    / __  / / /_  / /
@@ -35,19 +37,7 @@ impl<'a> TitleBarWidget<'a> {
 
 impl<'a> WidgetRef for TitleBarWidget<'a> {
     fn render_ref(&self, area: Rect, buf: &mut Buffer) {
-
-        let horizontal_layout = Layout::horizontal([
-            Constraint::Ratio(1, 2),
-            Constraint::Ratio(1, 2),
-        ]);
-
-        let vertical_layout = Layout::vertical([
-            Constraint::Ratio(1, 2),
-            Constraint::Ratio(1, 2),
-        ]);
-        let [app_area, logo_area] = horizontal_layout.areas(area);
-
-        let [app_title_area, state_title_area] = vertical_layout.areas(app_area);
+        let [app_title_area, state_title_area, logo_area] = split_title_bar(area);
 
         self.app_title.render_ref(app_title_area, buf);
         self.state_title.render_ref(state_title_area, buf);
@@ -56,19 +46,80 @@ impl<'a> WidgetRef for TitleBarWidget<'a> {
     }
 }
 
+fn split_title_bar(area: Rect) -> [Rect; 3] {
+    let horizontal_layout = Layout::horizontal([
+        Constraint::Ratio(1, 2),
+        Constraint::Ratio(1, 2),
+    ]);
+
+    let vertical_layout = Layout::vertical([
+        Constraint::Ratio(1, 2),
+        Constraint::Ratio(1, 2),
+    ]);
+    let [app_area, logo_area] = horizontal_layout.areas(area);
+
+    let [app_title_area, state_title_area] = vertical_layout.areas(app_area);
+    [app_title_area, state_title_area, logo_area]
+}
+
+/// Shows the running command in the row under the app title, which the title bar leaves free.
+/// Renders over a whole frame, as the frame of the flow stays on screen while the flow waits for the command.
+pub struct BusyIndicatorWidget<'a> {
+    text: &'a str,
+}
+
+impl<'a> BusyIndicatorWidget<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self { text }
+    }
+}
+
+impl<'a> WidgetRef for BusyIndicatorWidget<'a> {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let [title_bar_area, _] = Layout::vertical([Constraint::Length(TITLE_BAR_ROW_COUNT), Constraint::Min(0)]).areas(area);
+        let [app_title_area, _, _] = split_title_bar(title_bar_area);
+        let [_, indicator_area] = Layout::vertical([Constraint::Length(1), Constraint::Length(1)]).areas(app_title_area);
+
+        Clear.render_ref(indicator_area, buf);
+        Paragraph::new(self.text)
+            .style(Style::default().fg(Color::Yellow))
+            .render_ref(indicator_area, buf);
+    }
+}
+
+const BREADCRUMB_LENGTH: usize = 4;
+
 pub struct TitleBarViewModel {
     app_title: String,
     state_title: String,
 }
 
-impl <'a> TitleBarViewModel {
+impl TitleBarViewModel {
     pub fn new<T: Transit>(workflow: &Workflow<T>) -> Self {
         Self {
             app_title: workflow.get_app_title().to_string(),
-            state_title: workflow.get_state_title().to_string(),
+            state_title: Self::get_breadcrumb_title(workflow),
+        }
+    }
+
+    pub fn refresh(&mut self, workflow: &impl TerminalFlow) {
+        let current_state_title = Self::get_breadcrumb_title(workflow);
+        if self.state_title != current_state_title {
+            self.state_title = current_state_title
+        }
+    }
+
+    /// The most recent states that lead to the current one, e.g. `… › files › files › content`.
+    fn get_breadcrumb_title(workflow: &impl TerminalFlow) -> String {
+        let breadcrumb = workflow.get_breadcrumb();
+        let shown_states = &breadcrumb[breadcrumb.len().saturating_sub(BREADCRUMB_LENGTH)..];
+        let title = shown_states.join(" › ");
+        if shown_states.len() < breadcrumb.len() {
+            format!("… › {title}")
+        } else {
+            title
         }
     }
-    
 }
 
 impl InputUpdatedViewModel for TitleBarViewModel {
@@ -79,9 +130,6 @@ impl InputUpdatedViewModel for TitleBarViewModel {
     }
 
     fn update(&mut self, _: &mut Self::ViewState, workflow: &mut impl TerminalFlow, _: &Key) {
-        let current_state_title = workflow.get_state_title();
-        if self.state_title != *current_state_title {
-            self.state_title = current_state_title.to_string()
-        }
+        self.refresh(workflow);
     }
-}
\ No newline at end of file
+}